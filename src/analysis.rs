@@ -0,0 +1,362 @@
+// Black-box analysis of an encryption oracle: block size, mode, length of
+// the stuff it adds around our input, padding.
+
+use crate::oracle::EncryptionOracle;
+use num_integer::Integer;
+
+pub const MAX_BLOCK_SIZE: usize = 64;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AnalysisError {
+    // The same input gave outputs of different lengths
+    RandomLength,
+    // The output length didn't grow with the input the way block padding
+    // does: it shrank, or grew by more than the input
+    InconsistentLength,
+    // The output length didn't change for inputs up to MAX_BLOCK_SIZE bytes
+    NoLengthChange,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Padding {
+    // The last block is the encryption of a full block of PKCS#7 padding
+    // when the cleartext length is a multiple of the block size.
+    Pkcs7,
+    // The last block changes from one query to the next (with the rest
+    // of the output staying the same).
+    Randomized,
+    // Can't tell.
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Report {
+    pub block_size: usize,
+    pub ecb: bool,
+    // Same input, same output (ignoring the last block)
+    pub deterministic: bool,
+    // Total length of the prefix and suffix, unless it's random
+    pub extra_len: Option<usize>,
+    // Only known for deterministic oracles
+    pub prefix_len: Option<usize>,
+    pub suffix_len: Option<usize>,
+    pub padding: Padding,
+}
+
+impl Report {
+    pub fn has_prefix(&self) -> Option<bool> {
+        self.prefix_len.map(|len| len != 0)
+    }
+}
+
+// Find the block size of an ECB oracle.
+//
+// Send a long run of identical bytes: the output will contain a long run
+// of identical blocks, aligned on block boundaries. The block size is the
+// smallest chunk size for which this is the case. (Twice the block size
+// works too, but it's larger; smaller sizes would need long runs of
+// identical chunks of random-looking bytes, which don't happen by chance.)
+// This works even if the oracle adds a random-length prefix.
+pub fn detect_block_size<T: EncryptionOracle + ?Sized>(oracle: &T) -> Option<usize> {
+    let out = oracle.encrypt(&[0; 4 * MAX_BLOCK_SIZE]);
+    (1..=MAX_BLOCK_SIZE)
+        .filter(|&bs| out.len() % bs == 0)
+        .find(|&bs| longest_run(&out, bs) * bs >= 2 * MAX_BLOCK_SIZE)
+}
+
+// Length of the longest run of identical chunks of size bs in data
+fn longest_run(data: &[u8], bs: usize) -> usize {
+    let mut longest = 0;
+    let mut cur = 0;
+    let mut prev: &[u8] = &[];
+    for chunk in data.chunks_exact(bs) {
+        cur = if chunk == prev { cur + 1 } else { 1 };
+        longest = longest.max(cur);
+        prev = chunk;
+    }
+    longest
+}
+
+// Find the total length of what the oracle adds around our input,
+// and the block size, by watching the output length as the input grows.
+//
+// We need to know that the padding length is between 1 and block_size
+// (that is, a full block of padding is inserted if the length
+// before padding was already a multiple of block_size).
+pub fn lengths<T: EncryptionOracle + ?Sized>(oracle: &T) -> Result<(usize, usize), AnalysisError> {
+    lengths_with_filler(oracle, 0)
+}
+
+// Same as lengths(), growing the input with the given byte, for oracles
+// that don't accept arbitrary input. (The filler must end up as exactly
+// one byte in the cleartext.)
+pub fn lengths_with_filler<T: EncryptionOracle + ?Sized>(
+    oracle: &T,
+    filler: u8,
+) -> Result<(usize, usize), AnalysisError> {
+    try_lengths_with_filler(|input| Ok(oracle.encrypt(input)), filler)
+}
+
+// Same as lengths_with_filler(), with queries that can fail: stop at the
+// first error.
+pub fn try_lengths_with_filler<E, F>(mut encrypt: F, filler: u8) -> Result<(usize, usize), E>
+where
+    E: From<AnalysisError>,
+    F: FnMut(&[u8]) -> Result<Vec<u8>, E>,
+{
+    let base = encrypt(b"")?.len();
+    for _ in 0..8 {
+        if encrypt(b"")?.len() != base {
+            return Err(AnalysisError::RandomLength.into());
+        }
+    }
+
+    for n in 1..=MAX_BLOCK_SIZE {
+        let len = encrypt(&vec![filler; n])?.len();
+        if len < base {
+            return Err(AnalysisError::InconsistentLength.into());
+        }
+        let diff = len - base;
+        if diff != 0 {
+            // Last ciphertext had length base + diff and cleartext was:
+            // n bytes + extra + diff bytes of padding, so
+            // n + extra + diff = base + diff.
+            return base
+                .checked_sub(n)
+                .map(|extra| (extra, diff))
+                .ok_or(AnalysisError::InconsistentLength.into());
+        }
+    }
+    Err(AnalysisError::NoLengthChange.into())
+}
+
+// Block size of an oracle whose output length varies for the same input:
+// the output is always a whole number of blocks, so take the GCD of a few
+// lengths. Random lengths give the block size itself soon enough.
+fn random_length_block_size<T: EncryptionOracle + ?Sized>(oracle: &T) -> Option<usize> {
+    let block_size = (0..32).fold(0, |gcd, _| gcd.gcd(&oracle.encrypt(b"").len()));
+    (1..=MAX_BLOCK_SIZE)
+        .contains(&block_size)
+        .then_some(block_size)
+}
+
+// Index of the first block that differs
+fn first_diff_block(a: &[u8], b: &[u8], block_size: usize) -> Option<usize> {
+    std::iter::zip(a.chunks(block_size), b.chunks(block_size)).position(|(x, y)| x != y)
+}
+
+// Find the length of the prefix of a deterministic oracle.
+//
+// Our first byte lands in the first block that differs between
+// encryptions of [0] and [1]. Then grow a run of 0 in front of that
+// byte until the first difference moves to the next block: at that point
+// the prefix plus our 0s fill a whole number of blocks.
+fn prefix_len<T: EncryptionOracle + ?Sized>(oracle: &T, block_size: usize) -> Option<usize> {
+    let first_diff = |k: usize| {
+        let a = vec![0; k + 1];
+        let mut b = a.clone();
+        b[k] = 1;
+        first_diff_block(&oracle.encrypt(&a), &oracle.encrypt(&b), block_size)
+    };
+
+    let start = first_diff(0)?;
+    (1..=block_size)
+        .find(|&k| first_diff(k) == Some(start + 1))
+        .map(|k| (start + 1) * block_size - k)
+}
+
+// Same input twice, compare everything but the last block
+fn is_deterministic<T: EncryptionOracle + ?Sized>(oracle: &T, block_size: usize) -> bool {
+    let input = [0; 2 * MAX_BLOCK_SIZE];
+    let a = oracle.encrypt(&input);
+    let b = oracle.encrypt(&input);
+    a.len() == b.len() && a[..a.len() - block_size] == b[..b.len() - block_size]
+}
+
+fn padding<T: EncryptionOracle + ?Sized>(
+    oracle: &T,
+    block_size: usize,
+    ecb: bool,
+    prefix_len: Option<usize>,
+    extra_len: Option<usize>,
+) -> Padding {
+    // Make the cleartext a whole number of blocks, then compare the last
+    // block between two queries
+    let Some(extra_len) = extra_len else {
+        return Padding::Unknown;
+    };
+    let input = vec![0; block_size - extra_len % block_size];
+    let a = oracle.encrypt(&input);
+    let b = oracle.encrypt(&input);
+    let (a_last, b_last) = (&a[a.len() - block_size..], &b[b.len() - block_size..]);
+    if a[..a.len() - block_size] == b[..b.len() - block_size] && a_last != b_last {
+        return Padding::Randomized;
+    }
+
+    // With ECB, we can compare with the encryption of a full block of padding
+    if let (true, Some(prefix_len)) = (ecb, prefix_len) {
+        let fill = (block_size - prefix_len % block_size) % block_size;
+        let mut probe = vec![0; fill];
+        probe.resize(fill + block_size, block_size as u8);
+        let out = oracle.encrypt(&probe);
+        let start = prefix_len + fill;
+        if out[start..start + block_size] == *a_last {
+            return Padding::Pkcs7;
+        }
+    }
+
+    Padding::Unknown
+}
+
+// If the oracle adds a random-length prefix (or suffix), we can still
+// find the block size and whether it's ECB, but not the lengths.
+pub fn analyze<T: EncryptionOracle + ?Sized>(oracle: &T) -> Result<Report, AnalysisError> {
+    let (extra_len, block_size) = match lengths(oracle) {
+        Ok((extra_len, block_size)) => (Some(extra_len), block_size),
+        Err(AnalysisError::RandomLength) => (
+            None,
+            random_length_block_size(oracle).ok_or(AnalysisError::RandomLength)?,
+        ),
+        Err(err) => return Err(err),
+    };
+    let ecb = detect_block_size(oracle) == Some(block_size);
+    let deterministic = is_deterministic(oracle, block_size);
+    let prefix_len = match deterministic {
+        true => prefix_len(oracle, block_size),
+        false => None,
+    };
+    let suffix_len = prefix_len.and_then(|p| extra_len?.checked_sub(p));
+    let padding = padding(oracle, block_size, ecb, prefix_len, extra_len);
+
+    Ok(Report {
+        block_size,
+        ecb,
+        deterministic,
+        extra_len,
+        prefix_len,
+        suffix_len,
+        padding,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s1c07::aes_128_ecb_encrypt;
+    use crate::s2c09::pkcs7_pad;
+    use crate::s2c10::aes_128_cbc_encrypt;
+
+    // Fixed prefix and suffix, PKCS#7 padding, ECB or CBC with a fixed IV
+    struct TestOracle {
+        prefix: Vec<u8>,
+        suffix: Vec<u8>,
+        key: [u8; 16],
+        cbc: bool,
+    }
+
+    impl TestOracle {
+        fn new(prefix_len: usize, suffix_len: usize, cbc: bool) -> Self {
+            Self {
+                prefix: (0..prefix_len).map(|_| rand::random()).collect(),
+                suffix: (0..suffix_len).map(|_| rand::random()).collect(),
+                key: rand::random(),
+                cbc,
+            }
+        }
+    }
+
+    impl EncryptionOracle for TestOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            let mut clear = self.prefix.clone();
+            clear.extend_from_slice(input);
+            clear.extend_from_slice(&self.suffix);
+            let clear = pkcs7_pad(&clear, 16);
+            match self.cbc {
+                false => aes_128_ecb_encrypt(&self.key, &clear).unwrap(),
+                true => aes_128_cbc_encrypt(&self.key, &[0; 16], &clear).unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_prefix_suffix() {
+        for cbc in [false, true] {
+            for prefix_len in [0, 1, 15, 16, 17, 40] {
+                for suffix_len in [0, 1, 15, 16, 17, 33] {
+                    let oracle = TestOracle::new(prefix_len, suffix_len, cbc);
+                    let exp = Report {
+                        block_size: 16,
+                        ecb: !cbc,
+                        deterministic: true,
+                        extra_len: Some(prefix_len + suffix_len),
+                        prefix_len: Some(prefix_len),
+                        suffix_len: Some(suffix_len),
+                        padding: match cbc {
+                            false => Padding::Pkcs7,
+                            true => Padding::Unknown,
+                        },
+                    };
+                    assert_eq!(analyze(&oracle), Ok(exp));
+                    assert_eq!(exp.has_prefix(), Some(prefix_len != 0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn challenge_oracles() {
+        let content = b"Some secret content";
+        let oracle = crate::s2c12::Oracle::new(content);
+        let exp = Report {
+            block_size: 16,
+            ecb: true,
+            deterministic: true,
+            extra_len: Some(content.len()),
+            prefix_len: Some(0),
+            suffix_len: Some(content.len()),
+            padding: Padding::Randomized,
+        };
+        assert_eq!(analyze(&oracle), Ok(exp));
+
+        // Random-length prefix: only the block size and mode are known
+        let oracle = crate::s2c14::Oracle::new(content);
+        let exp = Report {
+            block_size: 16,
+            ecb: true,
+            deterministic: false,
+            extra_len: None,
+            prefix_len: None,
+            suffix_len: None,
+            padding: Padding::Unknown,
+        };
+        assert_eq!(analyze(&oracle), Ok(exp));
+        assert_eq!(exp.has_prefix(), None);
+        assert_eq!(detect_block_size(&oracle), Some(16));
+    }
+
+    // The prefix length changes every time
+    #[test]
+    fn block_size() {
+        let oracle = crate::s2c14::Oracle::new(b"");
+        for _ in 0..16 {
+            assert_eq!(detect_block_size(&oracle), Some(16));
+        }
+    }
+
+    // Output gets shorter as the input grows
+    struct ShrinkingOracle;
+
+    impl EncryptionOracle for ShrinkingOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            vec![0; 256 - 16 * input.len().min(16)]
+        }
+    }
+
+    #[test]
+    fn inconsistent_length() {
+        assert_eq!(
+            analyze(&ShrinkingOracle),
+            Err(AnalysisError::InconsistentLength)
+        );
+    }
+}
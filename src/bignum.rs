@@ -0,0 +1,235 @@
+// Big integers for the public-key challenges: a thin layer over num-bigint
+// with the number theory the attacks need.
+//
+// Everything works on non-negative BigUints; signed BigInts only show up
+// inside the extended Euclidean algorithm.
+
+pub use num_bigint::{BigInt, BigUint, RandBigInt};
+pub use num_integer::Integer;
+pub use num_traits::{One, ToPrimitive, Zero};
+
+use num_traits::Signed;
+use rand::thread_rng;
+
+// Big-endian bytes, as used on the wire
+pub fn from_bytes(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+// Big-endian bytes, left-padded with 0s to len bytes (or longer if n
+// doesn't fit)
+pub fn to_bytes(n: &BigUint, len: usize) -> Vec<u8> {
+    let bytes = n.to_bytes_be();
+    let mut out = vec![0u8; len.saturating_sub(bytes.len())];
+    out.extend_from_slice(&bytes);
+    out
+}
+
+// Parse a hex string, ignoring whitespace so that constants can be
+// copied from RFCs as is
+pub fn from_hex(s: &str) -> Option<BigUint> {
+    let digits: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    BigUint::parse_bytes(digits.as_bytes(), 16)
+}
+
+// base^exp mod m
+pub fn modexp(base: &BigUint, exp: &BigUint, m: &BigUint) -> BigUint {
+    base.modpow(exp, m)
+}
+
+// Returns (g, x, y) with a*x + b*y = g = gcd(a, b)
+pub fn egcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    let (mut old_r, mut r) = (a.clone(), b.clone());
+    let (mut old_x, mut x) = (BigInt::one(), BigInt::zero());
+    let (mut old_y, mut y) = (BigInt::zero(), BigInt::one());
+
+    while !r.is_zero() {
+        let q = &old_r / &r;
+        (old_r, r) = (r.clone(), old_r - &q * &r);
+        (old_x, x) = (x.clone(), old_x - &q * &x);
+        (old_y, y) = (y.clone(), old_y - &q * &y);
+    }
+
+    if old_r.is_negative() {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+// x such that a*x = 1 mod m, if a and m are coprime
+pub fn invmod(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    if m.is_zero() {
+        return None;
+    }
+    let m = BigInt::from(m.clone());
+    let (g, x, _) = egcd(&BigInt::from(a.clone()), &m);
+    if !g.is_one() {
+        return None;
+    }
+    x.mod_floor(&m).to_biguint()
+}
+
+// Largest r such that r^n <= x
+pub fn nth_root(x: &BigUint, n: u32) -> BigUint {
+    x.nth_root(n)
+}
+
+pub fn cube_root(x: &BigUint) -> BigUint {
+    nth_root(x, 3)
+}
+
+// r such that r^n = x, if there's one
+pub fn exact_nth_root(x: &BigUint, n: u32) -> Option<BigUint> {
+    let r = nth_root(x, n);
+    (r.pow(n) == *x).then_some(r)
+}
+
+// Chinese remainder theorem: given (residue, modulus) pairs with pairwise
+// coprime moduli, the unique x modulo the product of the moduli that has
+// all those residues
+pub fn crt(pairs: &[(BigUint, BigUint)]) -> Option<BigUint> {
+    let product: BigUint = pairs.iter().map(|(_, m)| m).product();
+
+    let mut x = BigUint::zero();
+    for (r, m) in pairs {
+        let ms = &product / m;
+        let inv = invmod(&(&ms % m), m)?;
+        x += r * &ms * inv;
+    }
+    Some(x % product)
+}
+
+const SMALL_PRIMES: [u32; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+// Miller-Rabin with rounds random bases, after some trial division.
+// A composite passes with probability at most 4^-rounds.
+pub fn is_probable_prime(n: &BigUint, rounds: usize) -> bool {
+    for p in SMALL_PRIMES {
+        let p = BigUint::from(p);
+        if *n == p {
+            return true;
+        }
+        if (n % &p).is_zero() {
+            return false;
+        }
+    }
+    if *n < BigUint::from(2u32) {
+        return false;
+    }
+
+    // n - 1 = d * 2^s with d odd
+    let one = BigUint::one();
+    let n_minus_1 = n - &one;
+    let s = n_minus_1.trailing_zeros().unwrap();
+    let d = &n_minus_1 >> s;
+
+    let mut rng = thread_rng();
+    'witness: for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&BigUint::from(2u32), &n_minus_1);
+        let mut x = modexp(&a, &d, n);
+        if x == one || x == n_minus_1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = modexp(&x, &BigUint::from(2u32), n);
+            if x == n_minus_1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+// Random prime of exactly bits bits
+pub fn gen_prime(bits: u64) -> BigUint {
+    assert!(bits >= 2);
+    let mut rng = thread_rng();
+    loop {
+        let mut candidate = rng.gen_biguint(bits);
+        candidate.set_bit(bits - 1, true);
+        candidate.set_bit(0, true);
+        if is_probable_prime(&candidate, 40) {
+            return candidate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(x: u64) -> BigUint {
+        BigUint::from(x)
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(from_bytes(&[1, 0]), n(256));
+        assert_eq!(to_bytes(&n(256), 4), [0, 0, 1, 0]);
+        assert_eq!(to_bytes(&n(256), 1), [1, 0]);
+        assert_eq!(from_hex("ff ff\n00"), Some(n(0xffff00)));
+        assert_eq!(from_hex("xyz"), None);
+    }
+
+    #[test]
+    fn exp() {
+        assert_eq!(modexp(&n(4), &n(13), &n(497)), n(445));
+        // Fermat
+        let p = (BigUint::one() << 127u32) - 1u32;
+        assert_eq!(modexp(&n(3), &(&p - 1u32), &p), BigUint::one());
+    }
+
+    #[test]
+    fn inverse() {
+        assert_eq!(invmod(&n(17), &n(3120)), Some(n(2753)));
+        assert_eq!(invmod(&n(3), &n(11)), Some(n(4)));
+        assert_eq!(invmod(&n(2), &n(4)), None);
+        assert_eq!(invmod(&n(5), &n(0)), None);
+
+        let (g, x, y) = egcd(&BigInt::from(240), &BigInt::from(46));
+        assert_eq!(g, BigInt::from(2));
+        assert_eq!(x * 240 + y * 46, g);
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(cube_root(&n(1000)), n(10));
+        assert_eq!(cube_root(&n(1001)), n(10));
+        assert_eq!(cube_root(&n(999)), n(9));
+
+        let x = from_hex("123456789abcdef0123456789abcdef").unwrap();
+        assert_eq!(exact_nth_root(&x.pow(5), 5), Some(x.clone()));
+        assert_eq!(exact_nth_root(&(x.pow(5) + 1u32), 5), None);
+    }
+
+    #[test]
+    fn chinese_remainders() {
+        // Sunzi's original problem
+        let pairs = [(n(2), n(3)), (n(3), n(5)), (n(2), n(7))];
+        assert_eq!(crt(&pairs), Some(n(23)));
+        assert_eq!(crt(&[(n(1), n(4)), (n(0), n(6))]), None);
+    }
+
+    #[test]
+    fn primes() {
+        for p in [2, 3, 97, 101, 7919, (1 << 61) - 1] {
+            assert!(is_probable_prime(&n(p), 20), "{}", p);
+        }
+        // Small factors, products of larger primes, and Carmichael numbers
+        // with no small factors (they fool Fermat but not Miller-Rabin),
+        // the last one even a strong pseudoprime to bases 2, 3, 5 and 7
+        for c in [0, 1, 561, 101 * 103, (1 << 32) + 1, 118901521, 3215031751] {
+            assert!(!is_probable_prime(&n(c), 20), "{}", c);
+        }
+        assert!(is_probable_prime(&((BigUint::one() << 127u32) - 1u32), 20));
+        assert!(!is_probable_prime(&((BigUint::one() << 128u32) + 1u32), 20));
+
+        let p = gen_prime(256);
+        assert_eq!(p.bits(), 256);
+        assert!(is_probable_prime(&p, 20));
+    }
+}
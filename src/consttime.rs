@@ -0,0 +1,180 @@
+// Constant-time helpers, for comparisons whose timing must not depend on
+// secret data (MACs, decrypted fields...), and a crude leak checker to
+// make sure they behave.
+//
+// Lengths are considered public: comparing slices of different lengths
+// returns early.
+
+use rand::{thread_rng, Rng};
+use std::hint::black_box;
+use std::time::Instant;
+
+// Compare two slices without stopping at the first difference
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = std::iter::zip(a, b).fold(0u8, |acc, (x, y)| black_box(acc | (x ^ y)));
+    ct_is_zero(diff)
+}
+
+fn ct_is_zero(x: u8) -> bool {
+    // 1 if x is 0, 0 otherwise, without branching on x
+    let x = x as u32;
+    (x.wrapping_sub(1) >> 31) == 1
+}
+
+// All 1s if choice, all 0s otherwise
+fn mask(choice: bool) -> u8 {
+    (black_box(choice as u8)).wrapping_neg()
+}
+
+// a if choice, b otherwise
+pub fn ct_select_u8(choice: bool, a: u8, b: u8) -> u8 {
+    let m = mask(choice);
+    (a & m) | (b & !m)
+}
+
+// a if choice, b otherwise; a and b must have the same length
+pub fn ct_select(choice: bool, a: &[u8], b: &[u8]) -> Vec<u8> {
+    assert_eq!(a.len(), b.len());
+    let m = mask(choice);
+    std::iter::zip(a, b)
+        .map(|(x, y)| (x & m) | (y & !m))
+        .collect()
+}
+
+// Result of check_leak(): Welch's t statistic between the timings of
+// the two classes of inputs, and whether it's large enough to say that
+// timing depends on the data.
+#[derive(Debug)]
+pub struct LeakReport {
+    pub t: f64,
+    pub leaky: bool,
+}
+
+// Same threshold as dudect: with |t| above it, the difference between
+// the two distributions is very unlikely to be chance.
+pub const T_THRESHOLD: f64 = 4.5;
+
+// Time compare(secret, input) on samples inputs, half of them equal to
+// the secret and half of them differing from the first byte, picking
+// the class at random each time so that noise affects both the same.
+// Then see whether the two timing distributions have different means.
+//
+// The slowest samples are dropped first, as they're mostly interrupts
+// and scheduling.
+//
+// None if there's nothing to compare (len is 0), or too few samples to
+// compute a variance for each class.
+pub fn check_leak<F>(compare: F, len: usize, samples: usize) -> Option<LeakReport>
+where
+    F: Fn(&[u8], &[u8]) -> bool,
+{
+    if len == 0 || samples < 4 {
+        return None;
+    }
+
+    let mut rng = thread_rng();
+    let secret: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+    let same = secret.clone();
+    let mut different: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+    different[0] = !secret[0];
+
+    let mut timings = [Vec::with_capacity(samples), Vec::with_capacity(samples)];
+    for _ in 0..samples {
+        let class = rng.gen_range(0..2);
+        let input = match class {
+            0 => &same,
+            _ => &different,
+        };
+        let start = Instant::now();
+        black_box(compare(black_box(&secret), black_box(input)));
+        timings[class].push(start.elapsed().as_nanos() as f64);
+    }
+
+    for t in timings.iter_mut() {
+        t.sort_by(f64::total_cmp);
+        t.truncate(t.len() * 9 / 10);
+    }
+    if timings.iter().any(|t| t.len() < 2) {
+        return None;
+    }
+
+    let t = welch_t(&timings[0], &timings[1]);
+    Some(LeakReport {
+        t,
+        leaky: t.abs() > T_THRESHOLD,
+    })
+}
+
+fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let mean_var = |x: &[f64]| {
+        let n = x.len() as f64;
+        let mean = x.iter().sum::<f64>() / n;
+        let var = x.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, var, n)
+    };
+    let (ma, va, na) = mean_var(a);
+    let (mb, vb, nb) = mean_var(b);
+
+    let se = (va / na + vb / nb).sqrt();
+    match se > 0.0 {
+        true => (ma - mb) / se,
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eq() {
+        assert!(ct_eq(b"", b""));
+        assert!(ct_eq(b"YELLOW SUBMARINE", b"YELLOW SUBMARINE"));
+        assert!(!ct_eq(b"YELLOW SUBMARINE", b"YELLOW SUBMARINF"));
+        assert!(!ct_eq(b"YELLOW SUBMARINE", b"yELLOW SUBMARINE"));
+        assert!(!ct_eq(b"YELLOW", b"YELLOW SUBMARINE"));
+        for x in 0..=255u8 {
+            assert_eq!(ct_is_zero(x), x == 0);
+        }
+    }
+
+    #[test]
+    fn select() {
+        assert_eq!(ct_select_u8(true, 0x12, 0x34), 0x12);
+        assert_eq!(ct_select_u8(false, 0x12, 0x34), 0x34);
+        assert_eq!(ct_select(true, b"abc", b"xyz"), b"abc");
+        assert_eq!(ct_select(false, b"abc", b"xyz"), b"xyz");
+    }
+
+    fn short_circuit(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && std::iter::zip(a, b).all(|(x, y)| black_box(x == y))
+    }
+
+    // 4096 byte comparisons against 1: noise doesn't hide that
+    #[test]
+    fn leaks() {
+        let report = check_leak(short_circuit, 4096, 20_000).unwrap();
+        assert!(report.leaky, "{:?}", report);
+    }
+
+    // Wall-clock statistics: with other tests running in parallel, noise
+    // can push t over the threshold. Run it on its own with --ignored.
+    #[test]
+    #[ignore]
+    fn no_leak() {
+        let report = check_leak(ct_eq, 4096, 20_000).unwrap();
+        assert!(!report.leaky, "{:?}", report);
+    }
+
+    #[test]
+    fn leak_args() {
+        assert!(check_leak(short_circuit, 0, 1000).is_none());
+        for samples in 0..4 {
+            assert!(check_leak(short_circuit, 16, samples).is_none());
+        }
+        assert!(check_leak(short_circuit, 1, 1000).is_some());
+    }
+}
@@ -1,3 +1,8 @@
+pub mod analysis;
+pub mod bignum;
+pub mod consttime;
+pub mod oracle;
+
 pub mod s1c01;
 pub mod s1c02;
 pub mod s1c03;
@@ -13,3 +18,35 @@ pub mod s2c11;
 pub mod s2c12;
 pub mod s2c13;
 pub mod s2c14;
+
+pub mod s3c18;
+
+pub mod s4c28;
+pub mod s4c29;
+pub mod s4c30;
+pub mod s4c31;
+pub mod s4c32;
+
+pub mod s5c33;
+pub mod s5c34;
+pub mod s5c35;
+pub mod s5c36;
+pub mod s5c37;
+pub mod s5c38;
+pub mod s5c39;
+pub mod s5c40;
+
+pub mod s6c41;
+pub mod s6c42;
+pub mod s6c43;
+pub mod s6c44;
+pub mod s6c45;
+pub mod s6c46;
+pub mod s6c47;
+
+pub mod s7c49;
+pub mod s7c50;
+pub mod s7c51;
+pub mod s7c52;
+pub mod s7c53;
+pub mod s7c54;
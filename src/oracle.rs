@@ -0,0 +1,89 @@
+// Traits shared by the oracles of the various challenges, so that attacks
+// can be written once and run against anything that behaves the same way:
+// the challenge oracles, wrappers around them, or stand-ins for a real
+// service.
+
+use std::cell::Cell;
+
+// Encrypts attacker-controlled input (possibly with extra stuff around it).
+pub trait EncryptionOracle {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8>;
+}
+
+// The same input always gets the same output.
+pub trait DeterministicOracle: EncryptionOracle {}
+
+// The output may vary from one call to the next for the same input,
+// for example because of a random IV, key or prefix.
+pub trait RandomizedOracle: EncryptionOracle {}
+
+// Decrypts attacker-controlled ciphertext, or refuses to.
+pub trait DecryptionOracle {
+    fn decrypt(&self, ct: &[u8]) -> Option<Vec<u8>>;
+}
+
+// Only tells whether a token / ciphertext is acceptable.
+pub trait ValidityOracle {
+    fn is_valid(&self, token: &[u8]) -> bool;
+}
+
+// Only leaks the length of what it would send for attacker-controlled
+// input.
+pub trait LengthOracle {
+    fn length(&self, input: &[u8]) -> usize;
+}
+
+// Wrap an oracle to count how many queries an attack makes.
+pub struct QueryCounter<'a, T: ?Sized> {
+    oracle: &'a T,
+    count: Cell<usize>,
+}
+
+impl<'a, T: ?Sized> QueryCounter<'a, T> {
+    pub fn new(oracle: &'a T) -> Self {
+        Self {
+            oracle,
+            count: Cell::new(0),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    fn inc(&self) {
+        self.count.set(self.count.get() + 1);
+    }
+}
+
+impl<T: EncryptionOracle + ?Sized> EncryptionOracle for QueryCounter<'_, T> {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        self.inc();
+        self.oracle.encrypt(input)
+    }
+}
+
+impl<T: DeterministicOracle + ?Sized> DeterministicOracle for QueryCounter<'_, T> {}
+
+impl<T: RandomizedOracle + ?Sized> RandomizedOracle for QueryCounter<'_, T> {}
+
+impl<T: DecryptionOracle + ?Sized> DecryptionOracle for QueryCounter<'_, T> {
+    fn decrypt(&self, ct: &[u8]) -> Option<Vec<u8>> {
+        self.inc();
+        self.oracle.decrypt(ct)
+    }
+}
+
+impl<T: ValidityOracle + ?Sized> ValidityOracle for QueryCounter<'_, T> {
+    fn is_valid(&self, token: &[u8]) -> bool {
+        self.inc();
+        self.oracle.is_valid(token)
+    }
+}
+
+impl<T: LengthOracle + ?Sized> LengthOracle for QueryCounter<'_, T> {
+    fn length(&self, input: &[u8]) -> usize {
+        self.inc();
+        self.oracle.length(input)
+    }
+}
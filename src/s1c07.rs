@@ -13,7 +13,7 @@ fn aes_128_ecb(key: &[u8], inp: &[u8], way: Way) -> Option<Vec<u8>> {
     let cipher = Aes128::new_from_slice(key).ok()?;
 
     let block_size = Aes128::block_size();
-    if inp.len() % block_size != 0 {
+    if !inp.len().is_multiple_of(block_size) {
         return None;
     }
 
@@ -56,7 +56,7 @@ mod tests {
         let ct = read_ct();
         let exp = std::fs::read("data/07-pt.txt").unwrap();
         assert!(ct.len() >= exp.len());
-        assert!(ct.len() / 16 == (exp.len() + 15) / 16);
+        assert!(ct.len() / 16 == exp.len().div_ceil(16));
 
         let key = b"YELLOW SUBMARINE";
         let mut got = aes_128_ecb_decrypt(key, &ct).unwrap();
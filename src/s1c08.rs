@@ -1,19 +1,147 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+
 pub fn find_ecb(cts: &[Vec<u8>]) -> Option<usize> {
     cts.iter()
         .enumerate()
-        .max_by_key(|(_, ct)| count_rep16(ct))
+        .max_by_key(|(_, ct)| count_repeats(ct, 16))
         .map(|(i, _)| i)
 }
 
-fn block16(ct: &[u8], i: usize) -> &[u8] {
-    &ct[(16 * i)..(16 * i + 16)]
+// Number of pairs of identical blocks (at block-aligned offsets)
+pub fn count_repeats(ct: &[u8], block_size: usize) -> usize {
+    if block_size == 0 {
+        return 0;
+    }
+    let mut seen: HashMap<&[u8], usize> = HashMap::new();
+    let mut pairs = 0;
+    for block in ct.chunks_exact(block_size) {
+        let count = seen.entry(block).or_insert(0);
+        pairs += *count;
+        *count += 1;
+    }
+    pairs
+}
+
+// A block value that appears more than once, and where
+#[derive(PartialEq, Debug)]
+pub struct Repeat {
+    pub block: Vec<u8>,
+    pub offsets: Vec<usize>,
 }
 
-pub fn count_rep16(ct: &[u8]) -> usize {
-    (0..(ct.len() / 16))
-        .flat_map(|i| (0..i).map(move |j| (i, j)))
-        .filter(|(i, j)| block16(ct, *i) == block16(ct, *j))
-        .count()
+// Find repeated blocks at all alignments, so that it works even if
+// the ECB ciphertext doesn't start at a multiple of the block size.
+// Blocks are only compared to blocks with the same alignment.
+pub fn find_repeats(data: &[u8], block_size: usize) -> Vec<Repeat> {
+    let mut repeats = Vec::new();
+    for align in 0..block_size.min(data.len()) {
+        let mut seen: HashMap<&[u8], Vec<usize>> = HashMap::new();
+        for (i, block) in data[align..].chunks_exact(block_size).enumerate() {
+            seen.entry(block).or_default().push(align + i * block_size);
+        }
+        repeats.extend(
+            seen.into_iter()
+                .filter(|(_, offsets)| offsets.len() > 1)
+                .map(|(block, offsets)| Repeat {
+                    block: block.to_vec(),
+                    offsets,
+                }),
+        );
+    }
+    repeats.sort_by_key(|r| r.offsets[0]);
+    repeats
+}
+
+// Part of a stream that contains repeated blocks
+#[derive(PartialEq, Debug)]
+pub struct Region {
+    // from the start of the first repeated block
+    pub start: u64,
+    // to the end of the last one
+    pub end: u64,
+    // number of blocks that are a repeat of an earlier one
+    pub repeats: usize,
+}
+
+// Scan a stream (say a multi-gigabyte capture file) for regions that look
+// ECB-encrypted.
+//
+// Memory use is bounded by looking for repeats within a window of
+// window_size bytes at a time. Each window overlaps the previous one by
+// half, so repeats up to window_size / 2 bytes apart are found wherever
+// the window boundaries fall; only blocks in the new half are counted, so
+// that none are counted twice. Regions spanning several consecutive
+// windows are merged.
+pub fn scan<R: Read>(
+    mut reader: R,
+    block_size: usize,
+    window_size: usize,
+) -> io::Result<Vec<Region>> {
+    if block_size == 0 || window_size < 2 * block_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the window must hold at least two blocks",
+        ));
+    }
+
+    let mut regions: Vec<Region> = Vec::new();
+    let mut buf = Vec::with_capacity(window_size);
+    let mut base = 0u64;
+    let mut prev_had_repeats = false;
+
+    loop {
+        // Keep the second half of the previous window
+        let kept = buf.len();
+        reader
+            .by_ref()
+            .take((window_size - kept) as u64)
+            .read_to_end(&mut buf)?;
+        if buf.len() == kept {
+            break;
+        }
+
+        // Blocks in the new half that repeat an earlier one, from the
+        // earliest block they repeat
+        let mut first = usize::MAX;
+        let mut last = 0;
+        let mut count = 0;
+        for repeat in find_repeats(&buf, block_size) {
+            let new: Vec<_> = repeat.offsets[1..]
+                .iter()
+                .filter(|&&offset| offset + block_size > kept)
+                .collect();
+            if let Some(&&offset) = new.last() {
+                first = first.min(repeat.offsets[0]);
+                last = last.max(offset);
+                count += new.len();
+            }
+        }
+
+        let had_repeats = count > 0;
+        if had_repeats {
+            let region = Region {
+                start: base + first as u64,
+                end: base + (last + block_size) as u64,
+                repeats: count,
+            };
+            match regions.last_mut() {
+                Some(prev) if prev_had_repeats => {
+                    prev.start = prev.start.min(region.start);
+                    prev.end = prev.end.max(region.end);
+                    prev.repeats += region.repeats;
+                }
+                _ => regions.push(region),
+            }
+        }
+        prev_had_repeats = had_repeats;
+
+        let drop = buf.len() - buf.len().min(window_size / 2);
+        buf.drain(..drop);
+        base += drop as u64;
+    }
+
+    Ok(regions)
 }
 
 #[cfg(test)]
@@ -42,4 +170,75 @@ mod tests {
         let idx = find_ecb(&cts);
         assert_eq!(idx, None);
     }
+
+    #[test]
+    fn repeats() {
+        let ct = b"xABCDABCDyyyyABCDzzz";
+        assert_eq!(count_repeats(ct, 4), 0);
+        assert_eq!(count_repeats(&ct[1..], 4), 3);
+        assert_eq!(count_repeats(&[7; 16], 4), 6);
+
+        let exp = vec![Repeat {
+            block: b"ABCD".to_vec(),
+            offsets: vec![1, 5, 13],
+        }];
+        assert_eq!(find_repeats(ct, 4), exp);
+        assert_eq!(find_repeats(b"", 4), vec![]);
+    }
+
+    #[test]
+    fn stream() {
+        use crate::s1c07::aes_128_ecb_encrypt;
+        use rand::{thread_rng, Rng};
+
+        let mut data = vec![0u8; 100_000];
+        thread_rng().fill(&mut data[..]);
+
+        // Something with a lot of structure, like an uncompressed image
+        let pt: Vec<u8> = (0..4096).map(|i| (i / 64 % 3) as u8).collect();
+        let ct = aes_128_ecb_encrypt(b"YELLOW SUBMARINE", &pt).unwrap();
+        let start = 30_001;
+        data.splice(start..start, ct.iter().copied());
+
+        let regions = scan(&data[..], 16, 1024).unwrap();
+        assert_eq!(regions.len(), 1);
+        let region = &regions[0];
+        assert!(region.start >= start as u64);
+        assert!(region.end <= (start + ct.len()) as u64);
+        assert!(region.repeats > 0);
+
+        let all = find_repeats(&ct, 16);
+        let first = all.iter().flat_map(|r| &r.offsets).min().unwrap();
+        assert_eq!(region.start, (start + first) as u64);
+    }
+
+    // Two copies of a block on each side of a window boundary
+    #[test]
+    fn stream_boundary() {
+        use rand::{thread_rng, Rng};
+
+        let mut data = vec![0u8; 4096];
+        thread_rng().fill(&mut data[..]);
+        let block: [u8; 16] = thread_rng().gen();
+        data[1000..1016].copy_from_slice(&block);
+        data[1032..1048].copy_from_slice(&block);
+
+        let regions = scan(&data[..], 16, 1024).unwrap();
+        let exp = Region {
+            start: 1000,
+            end: 1048,
+            repeats: 1,
+        };
+        assert_eq!(regions, vec![exp]);
+    }
+
+    #[test]
+    fn stream_args() {
+        for (block_size, window_size) in [(0, 1024), (16, 0), (16, 31)] {
+            let err = scan(&b"hello"[..], block_size, window_size).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(scan(&b""[..], 16, 32).unwrap(), vec![]);
+        assert_eq!(count_repeats(b"hello", 0), 0);
+    }
 }
@@ -12,6 +12,25 @@ pub fn pkcs7_pad(raw: &[u8], block_size: usize) -> Vec<u8> {
     padded
 }
 
+// Check and remove PKCS#7 padding
+pub fn pkcs7_unpad(padded: &[u8], block_size: usize) -> Option<Vec<u8>> {
+    if padded.is_empty() || !padded.len().is_multiple_of(block_size) {
+        return None;
+    }
+
+    let pad_value = *padded.last()?;
+    let pad_len = pad_value as usize;
+    if pad_len == 0 || pad_len > block_size {
+        return None;
+    }
+
+    let (raw, pad) = padded.split_at(padded.len() - pad_len);
+    match pad.iter().all(|&b| b == pad_value) {
+        true => Some(raw.to_vec()),
+        false => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +60,19 @@ mod tests {
         assert_eq!(pkcs7_pad(&in10, 5), out5);
         assert_eq!(pkcs7_pad(&in10, 6), out6);
     }
+
+    #[test]
+    fn unpad() {
+        for len in 0..=33 {
+            let raw = vec![42; len];
+            assert_eq!(pkcs7_unpad(&pkcs7_pad(&raw, 16), 16), Some(raw));
+        }
+
+        assert_eq!(pkcs7_unpad(b"", 4), None);
+        assert_eq!(pkcs7_unpad(b"abc\x01", 8), None);
+        assert_eq!(pkcs7_unpad(b"abc\x00", 4), None);
+        assert_eq!(pkcs7_unpad(b"abc\x05", 4), None);
+        assert_eq!(pkcs7_unpad(b"ab\x01\x02", 4), None);
+        assert_eq!(pkcs7_unpad(b"ab\x02\x02", 4), Some(b"ab".to_vec()));
+    }
 }
@@ -6,7 +6,7 @@ use std::iter::zip;
 
 fn check_lengths(iv: &[u8], data: &[u8]) -> Option<usize> {
     let block_size = Aes128::block_size();
-    if data.len() % block_size != 0 || iv.len() != block_size {
+    if !data.len().is_multiple_of(block_size) || iv.len() != block_size {
         None
     } else {
         Some(block_size)
@@ -66,7 +66,7 @@ mod tests {
         let ct = read_ct();
         let exp = std::fs::read("data/10-pt.txt").unwrap();
         assert!(ct.len() >= exp.len());
-        assert!(ct.len() / 16 == (exp.len() + 15) / 16);
+        assert!(ct.len() / 16 == exp.len().div_ceil(16));
 
         let key = b"YELLOW SUBMARINE";
         let iv = [0; 16];
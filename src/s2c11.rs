@@ -2,24 +2,48 @@
 pub enum Mode {
     ECB,
     CBC,
+    CTR,
+    OFB,
+    CFB,
+    // Not built from a block cipher
+    Stream,
 }
 
+pub const ALL_MODES: [Mode; 6] = [
+    Mode::ECB,
+    Mode::CBC,
+    Mode::CTR,
+    Mode::OFB,
+    Mode::CFB,
+    Mode::Stream,
+];
+
 impl Mode {
+    // Only ECB or CBC, as in the challenge
     pub fn rand() -> Self {
         match rand::random() {
             false => Mode::ECB,
             true => Mode::CBC,
         }
     }
+
+    // Output length is rounded up to a whole number of blocks
+    pub fn is_padded(self) -> bool {
+        matches!(self, Mode::ECB | Mode::CBC)
+    }
 }
 
 // use a separate module for privacy
 mod oracle {
     use super::Mode;
+    use crate::oracle::{DeterministicOracle, EncryptionOracle, RandomizedOracle};
     use crate::s1c07::aes_128_ecb_encrypt;
     use crate::s2c09::pkcs7_pad;
     use crate::s2c10::aes_128_cbc_encrypt;
-    use rand::{thread_rng, Rng};
+    use crate::s3c18::aes_128_ctr;
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, Rng, RngCore, SeedableRng};
+    use std::iter::zip;
 
     pub struct Oracle {
         mode: Mode,
@@ -34,12 +58,63 @@ mod oracle {
         }
     }
 
+    fn xor_keystream(data: &[u8], keystream: &[u8]) -> Vec<u8> {
+        zip(data, keystream).map(|(x, y)| x ^ y).collect()
+    }
+
+    fn aes_128_ofb_encrypt(key: &[u8], iv: &[u8], pt: &[u8]) -> Vec<u8> {
+        let mut keystream = Vec::with_capacity(pt.len() + 16);
+        let mut block = iv.to_vec();
+        while keystream.len() < pt.len() {
+            block = aes_128_ecb_encrypt(key, &block).unwrap();
+            keystream.extend_from_slice(&block);
+        }
+        xor_keystream(pt, &keystream)
+    }
+
+    // Full-block CFB: a partial last block is simply truncated
+    fn aes_128_cfb_encrypt(key: &[u8], iv: &[u8], pt: &[u8]) -> Vec<u8> {
+        let mut ct = Vec::with_capacity(pt.len());
+        let mut prev = iv.to_vec();
+        for pt_block in pt.chunks(16) {
+            let keystream = aes_128_ecb_encrypt(key, &prev).unwrap();
+            let ct_block = xor_keystream(pt_block, &keystream);
+            ct.extend_from_slice(&ct_block);
+            prev = ct_block;
+        }
+        ct
+    }
+
+    // Use rand's StdRng (currently ChaCha12) as a stream cipher
+    fn stream_encrypt(key: &[u8], iv: &[u8], pt: &[u8]) -> Vec<u8> {
+        let mut seed = [0u8; 32];
+        seed[..16].copy_from_slice(key);
+        seed[16..].copy_from_slice(iv);
+        let mut keystream = vec![0; pt.len()];
+        StdRng::from_seed(seed).fill_bytes(&mut keystream);
+        xor_keystream(pt, &keystream)
+    }
+
+    fn encrypt(mode: Mode, key: &[u8; 16], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+        let nonce = u64::from_le_bytes(iv[..8].try_into().unwrap());
+        match mode {
+            Mode::ECB => aes_128_ecb_encrypt(key, &pkcs7_pad(data, 16)).unwrap(),
+            Mode::CBC => aes_128_cbc_encrypt(key, iv, &pkcs7_pad(data, 16)).unwrap(),
+            Mode::CTR => aes_128_ctr(key, nonce, data).unwrap(),
+            Mode::OFB => aes_128_ofb_encrypt(key, iv, data),
+            Mode::CFB => aes_128_cfb_encrypt(key, iv, data),
+            Mode::Stream => stream_encrypt(key, iv, data),
+        }
+    }
+
     impl Oracle {
         pub fn new(mode: Mode) -> Self {
             Self { mode }
         }
+    }
 
-        pub fn process(&self, input: &[u8]) -> Vec<u8> {
+    impl EncryptionOracle for Oracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
             let mut rng = thread_rng();
 
             let mut key = [0u8; 16];
@@ -52,25 +127,65 @@ mod oracle {
             add_rand(&mut data);
             data.extend_from_slice(input);
             add_rand(&mut data);
-            let data = pkcs7_pad(&data, 16);
 
-            match self.mode {
-                Mode::ECB => aes_128_ecb_encrypt(&key, &data).unwrap(),
-                Mode::CBC => aes_128_cbc_encrypt(&key, &iv, &data).unwrap(),
+            encrypt(self.mode, &key, &iv, &data)
+        }
+    }
+
+    impl RandomizedOracle for Oracle {}
+
+    // Same, but the key, IV and random stuff around the input are chosen
+    // once and for all, as in a service that (wrongly) reuses its IV.
+    pub struct FixedKeyOracle {
+        mode: Mode,
+        key: [u8; 16],
+        iv: [u8; 16],
+        prefix: Vec<u8>,
+        suffix: Vec<u8>,
+    }
+
+    impl FixedKeyOracle {
+        pub fn new(mode: Mode) -> Self {
+            let mut prefix = Vec::new();
+            add_rand(&mut prefix);
+            let mut suffix = Vec::new();
+            add_rand(&mut suffix);
+
+            Self {
+                mode,
+                key: rand::random(),
+                iv: rand::random(),
+                prefix,
+                suffix,
             }
         }
     }
+
+    impl EncryptionOracle for FixedKeyOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            let mut data = self.prefix.clone();
+            data.extend_from_slice(input);
+            data.extend_from_slice(&self.suffix);
+
+            encrypt(self.mode, &self.key, &self.iv, &data)
+        }
+    }
+
+    impl DeterministicOracle for FixedKeyOracle {}
 }
 
-use crate::s1c08::count_rep16;
-use oracle::Oracle;
+use crate::analysis::MAX_BLOCK_SIZE;
+use crate::oracle::EncryptionOracle;
+use crate::s1c08::count_repeats;
+use num_integer::Integer;
+pub use oracle::{FixedKeyOracle, Oracle};
 
 // guess which mode is used by the Oracle
 // victim.mode is private and can't be read
-pub fn attack(victim: &Oracle) -> Mode {
+pub fn attack<T: EncryptionOracle + ?Sized>(victim: &T) -> Mode {
     let three_identical_blocks = [0; 48];
-    let out = victim.process(&three_identical_blocks);
-    let repeated_blocks = count_rep16(&out);
+    let out = victim.encrypt(&three_identical_blocks);
+    let repeated_blocks = count_repeats(&out, 16);
 
     match repeated_blocks {
         0 => Mode::CBC,
@@ -78,6 +193,106 @@ pub fn attack(victim: &Oracle) -> Mode {
     }
 }
 
+// Probability of each mode, given what we observed
+#[derive(Debug)]
+pub struct Classification {
+    probs: Vec<(Mode, f64)>,
+}
+
+impl Classification {
+    pub fn prob(&self, mode: Mode) -> f64 {
+        self.probs.iter().find(|(m, _)| *m == mode).unwrap().1
+    }
+
+    // Most likely mode (the first one in case of a tie)
+    pub fn best(&self) -> Mode {
+        let max = self.probs.iter().map(|(_, p)| *p).fold(0.0, f64::max);
+        self.probs.iter().find(|(_, p)| *p == max).unwrap().0
+    }
+}
+
+// Likelihood of observing something a mode doesn't explain
+// (bad luck with random values, or some variant we don't know about)
+const MISMATCH: f64 = 0.001;
+
+// Guess the mode used by any oracle, with a probability for each mode.
+//
+// Start with all modes equally likely, then for each observation scale
+// down the modes that don't explain it. Observations:
+// - length: with ECB and CBC it goes by whole blocks, with other modes
+//   it goes up one byte at a time;
+// - repeated input blocks give repeated output blocks only with ECB;
+// - if the same input always gives the same output (fixed key and IV),
+//   see what flipping one input bit does to the output: ECB changes one
+//   block, CBC everything from that block onwards, CFB that bit then
+//   everything from the next block onwards, others only that bit.
+// CTR, OFB and stream ciphers all XOR the input with a keystream that
+// doesn't depend on it, so we can't tell them apart from the outside:
+// they end up equally likely.
+pub fn classify<T: EncryptionOracle + ?Sized>(victim: &T) -> Classification {
+    let mut probs: Vec<(Mode, f64)> = ALL_MODES.iter().map(|&m| (m, 1.0)).collect();
+    let mut observe = |fits: &dyn Fn(Mode) -> bool| {
+        for (mode, p) in probs.iter_mut() {
+            if !fits(*mode) {
+                *p *= MISMATCH;
+            }
+        }
+    };
+
+    let block_size = (0..=MAX_BLOCK_SIZE)
+        .map(|n| victim.encrypt(&vec![0; n]).len())
+        .fold(0, |gcd, len| gcd.gcd(&len));
+    let padded = block_size > 1;
+    observe(&|m| m.is_padded() == padded);
+
+    if padded {
+        let out = victim.encrypt(&[0; 3 * MAX_BLOCK_SIZE]);
+        let ecb = count_repeats(&out, block_size) > 0;
+        observe(&|m| (m == Mode::ECB) == ecb);
+    }
+
+    let input = [0; 4 * MAX_BLOCK_SIZE];
+    let out = victim.encrypt(&input);
+    if victim.encrypt(&input) == out {
+        let flip = |mask: u8| {
+            let mut flipped = input;
+            flipped[MAX_BLOCK_SIZE] ^= mask;
+            victim.encrypt(&flipped)
+        };
+        let out_flipped = flip(0x01);
+
+        let diffs: Vec<_> = std::iter::zip(&out, &out_flipped)
+            .enumerate()
+            .filter(|(_, (x, y))| x != y)
+            .map(|(i, _)| i)
+            .collect();
+        if let (Some(&first), Some(&last)) = (diffs.first(), diffs.last()) {
+            let spread = last - first + 1;
+            // The last bytes may happen to be the same despite the
+            // change, and without padding block_size is 1: leave some slack
+            let to_end = last + block_size.max(8) >= out.len();
+            // With ECB and CBC that byte is random, and changes by
+            // exactly the right amount 1 time in 256: try another value
+            // to make sure
+            let same_bit =
+                out[first] ^ out_flipped[first] == 0x01 && out[first] ^ flip(0xa5)[first] == 0xa5;
+            observe(&|m| match m {
+                Mode::ECB => !same_bit && first / block_size == last / block_size,
+                Mode::CBC => !same_bit && spread > block_size && to_end,
+                Mode::CFB => same_bit && spread > 1 && to_end,
+                Mode::CTR | Mode::OFB | Mode::Stream => same_bit && spread == 1,
+            });
+        }
+    }
+
+    let total: f64 = probs.iter().map(|(_, p)| p).sum();
+    for (_, p) in probs.iter_mut() {
+        *p /= total;
+    }
+
+    Classification { probs }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,4 +305,45 @@ mod tests {
             assert_eq!(attack(&oracle), mode);
         }
     }
+
+    #[test]
+    fn other_oracle() {
+        let oracle = crate::s2c12::Oracle::new(b"some secret");
+        assert_eq!(attack(&oracle), Mode::ECB);
+    }
+
+    const KEYSTREAM_MODES: [Mode; 3] = [Mode::CTR, Mode::OFB, Mode::Stream];
+
+    #[test]
+    fn classify_fixed_key() {
+        for _ in 0..8 {
+            for mode in ALL_MODES {
+                let c = classify(&FixedKeyOracle::new(mode));
+                if KEYSTREAM_MODES.contains(&mode) {
+                    let p: f64 = KEYSTREAM_MODES.iter().map(|&m| c.prob(m)).sum();
+                    assert!(p > 0.99);
+                    assert!((c.prob(mode) - 1.0 / 3.0).abs() < 0.01);
+                } else {
+                    assert_eq!(c.best(), mode);
+                    assert!(c.prob(mode) > 0.99);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn classify_random_key() {
+        for _ in 0..8 {
+            for mode in ALL_MODES {
+                let c = classify(&Oracle::new(mode));
+                if mode.is_padded() {
+                    assert_eq!(c.best(), mode);
+                    assert!(c.prob(mode) > 0.99);
+                } else {
+                    // Can't see bit flips, so CFB looks like the others
+                    assert!((c.prob(mode) - 0.25).abs() < 0.01);
+                }
+            }
+        }
+    }
 }
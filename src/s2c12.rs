@@ -1,10 +1,10 @@
-pub trait DeterministicOracle {
-    fn process(&self, input: &[u8]) -> Vec<u8>;
-}
+use crate::analysis::{lengths, lengths_with_filler, try_lengths_with_filler, AnalysisError};
+use crate::oracle::DeterministicOracle;
+use std::collections::HashMap;
 
 // use a separate module for privacy
 mod oracle {
-    use super::DeterministicOracle;
+    use crate::oracle::{DeterministicOracle, EncryptionOracle};
     use crate::s1c07::aes_128_ecb_encrypt;
     use rand::{thread_rng, Rng};
 
@@ -24,8 +24,8 @@ mod oracle {
         }
     }
 
-    impl DeterministicOracle for Oracle {
-        fn process(&self, input: &[u8]) -> Vec<u8> {
+    impl EncryptionOracle for Oracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
             let mut clear = Vec::new();
             clear.extend_from_slice(input);
             clear.extend_from_slice(&self.content);
@@ -41,33 +41,31 @@ mod oracle {
             aes_128_ecb_encrypt(&self.key, &clear).unwrap()
         }
     }
+
+    impl DeterministicOracle for Oracle {}
 }
 
 pub use oracle::Oracle;
 
-// Find the length of the content hidden in the Oracle.
-// We need to know that the padding length is between 1 and block_size
-// (that is, a full block of padding is inserted if the length
-// before padding was already a multiple of block_size).
-fn attack_len<T: DeterministicOracle>(victim: &T) -> (usize, usize) {
-    let base = victim.process(b"").len();
-    let mut mybytes = vec![0];
-    loop {
-        let diff = victim.process(&mybytes).len() - base;
-        if diff != 0 {
-            // Last ciphertext had length base + diff and cleartext was:
-            // mybytes + content + diff bytes of padding, so
-            // len + content_len + diff = base + diff.
-            return (base - mybytes.len(), diff);
-        }
-        mybytes.push(0);
-    }
+// Find the length of the content hidden in the Oracle, and the block size.
+fn attack_len<T: DeterministicOracle + ?Sized>(victim: &T) -> (usize, usize) {
+    lengths(victim).unwrap()
 }
 
 // Find the content hidden in the Oracle
 // victim.content is private and can't be read
-pub fn attack<T: DeterministicOracle>(victim: &T) -> Vec<u8> {
-    let (len, block_size) = attack_len(victim);
+pub fn attack<T: DeterministicOracle + ?Sized>(victim: &T) -> Vec<u8> {
+    try_attack(|input| Ok::<_, AnalysisError>(victim.encrypt(input))).unwrap()
+}
+
+// Same as attack(), with queries that can fail, for oracles that are only
+// deterministic as long as they work: stop at the first error.
+pub fn try_attack<E, F>(mut encrypt: F) -> Result<Vec<u8>, E>
+where
+    E: From<AnalysisError>,
+    F: FnMut(&[u8]) -> Result<Vec<u8>, E>,
+{
+    let (len, block_size) = try_lengths_with_filler(&mut encrypt, 0)?;
     println!("final length: {}", len);
     let mut content = Vec::with_capacity(len);
 
@@ -81,14 +79,15 @@ pub fn attack<T: DeterministicOracle>(victim: &T) -> Vec<u8> {
         // Establish a reference where the last byte of the block
         // is the one the next byte to guess from the content.
         let target_len = input.len() + content.len() + 1;
-        let target = &victim.process(&input)[..target_len];
+        let mut target = encrypt(&input)?;
+        target.truncate(target_len);
 
         // Append what we already know then
         // try all possible values for the last byte.
         input.extend_from_slice(&content);
         for b in 0u8..=255 {
             input.push(b);
-            if victim.process(&input)[..target.len()] == *target {
+            if encrypt(&input)?[..target.len()] == *target {
                 content.push(b);
                 break;
             }
@@ -96,12 +95,165 @@ pub fn attack<T: DeterministicOracle>(victim: &T) -> Vec<u8> {
         }
     }
 
-    content
+    Ok(content)
+}
+
+// Same as attack() but with far fewer queries.
+//
+// Instead of trying candidates one by one, put the 256 possible blocks
+// (known bytes followed by each possible value) in a single query, and
+// look up the target block in the resulting table.
+// The target blocks don't depend on what we've already found, so get
+// them all upfront: with pad_len bytes in front of the content, every
+// byte at offset block_size - 1 - pad_len modulo block_size ends a block,
+// so block_size queries cover all offsets.
+//
+// None if a target block isn't in the table, which happens when the
+// oracle isn't as deterministic as it claims.
+pub fn attack_dict<T: DeterministicOracle + ?Sized>(victim: &T) -> Option<Vec<u8>> {
+    let (len, block_size) = attack_len(victim);
+    let mut content = Vec::with_capacity(len);
+
+    let targets: Vec<_> = (0..block_size)
+        .map(|pad_len| victim.encrypt(&vec![0; pad_len]))
+        .collect();
+
+    // Known cleartext, starting with the 0s we used for padding
+    let mut known = vec![0; block_size - 1];
+    let mut input = Vec::with_capacity(256 * block_size);
+    while content.len() != len {
+        let window = &known[known.len() - (block_size - 1)..];
+        input.clear();
+        for b in 0u8..=255 {
+            input.extend_from_slice(window);
+            input.push(b);
+        }
+        let out = victim.encrypt(&input);
+        let dict: HashMap<_, _> = out.chunks_exact(block_size).zip(0u8..=255).collect();
+
+        let pad_len = block_size - 1 - content.len() % block_size;
+        let start = (pad_len + content.len()) / block_size * block_size;
+        let target = targets[pad_len].get(start..start + block_size)?;
+        let b = *dict.get(target)?;
+
+        content.push(b);
+        known.push(b);
+    }
+
+    Some(content)
+}
+
+// What attack_restricted() found: the content up to the first byte it
+// couldn't pin down, and the values that byte can still take. next is
+// empty if the content was recovered in full.
+#[derive(Debug, PartialEq)]
+pub struct Restricted {
+    pub content: Vec<u8>,
+    pub next: Vec<u8>,
+}
+
+// Same as attack_dict(), against an oracle that filters or escapes its
+// input, such as challenge 13's.
+//
+// We only send bytes from alphabet, and encode tells what each of them
+// becomes in the cleartext. Blocks can only be built out of cleartext
+// bytes that some input byte encodes to on its own: call the others
+// unproducible.
+//
+// charset is what the content can be made of. When the next byte isn't
+// any of the producible ones, it's one of the unproducible bytes in
+// charset: if there's only one, that's it. Either way, we can't build
+// the blocks that end with the bytes after it, since they have it in
+// their window, so stop there.
+pub fn attack_restricted<T, F>(
+    victim: &T,
+    alphabet: &[u8],
+    encode: F,
+    charset: &[u8],
+) -> Option<Restricted>
+where
+    T: DeterministicOracle + ?Sized,
+    F: Fn(u8) -> Vec<u8>,
+{
+    // Map cleartext bytes to the input byte that produces them
+    let producible: HashMap<u8, u8> = alphabet
+        .iter()
+        .filter_map(|&b| match encode(b)[..] {
+            [c] => Some((c, b)),
+            _ => None,
+        })
+        .collect();
+    let (&filler_clear, &filler) = producible.iter().min()?;
+
+    let mut unproducible: Vec<_> = charset
+        .iter()
+        .copied()
+        .filter(|c| !producible.contains_key(c))
+        .collect();
+    unproducible.sort();
+    unproducible.dedup();
+
+    let (len, block_size) = lengths_with_filler(victim, filler).ok()?;
+    let mut content = Vec::with_capacity(len);
+
+    let targets: Vec<_> = (0..block_size)
+        .map(|pad_len| victim.encrypt(&vec![filler; pad_len]))
+        .collect();
+
+    // The candidates are the same for every byte
+    let candidates: Vec<_> = producible.iter().collect();
+    let mut known = vec![filler_clear; block_size - 1];
+    let mut input = Vec::with_capacity(256 * block_size);
+    while content.len() != len {
+        let Some(window) = known[known.len() - (block_size - 1)..]
+            .iter()
+            .map(|c| producible.get(c).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            let mut next = charset.to_vec();
+            next.sort();
+            next.dedup();
+            return Some(Restricted { content, next });
+        };
+        input.clear();
+        for (_, &b) in &candidates {
+            input.extend_from_slice(&window);
+            input.push(b);
+        }
+        let out = victim.encrypt(&input);
+        let dict: HashMap<_, _> = out
+            .chunks_exact(block_size)
+            .zip(candidates.iter().map(|(&c, _)| c))
+            .collect();
+
+        let pad_len = block_size - 1 - content.len() % block_size;
+        let start = (pad_len + content.len()) / block_size * block_size;
+        let target = &targets[pad_len][start..start + block_size];
+        let c = match (dict.get(target), &unproducible[..]) {
+            (Some(&c), _) => c,
+            (None, &[c]) => c,
+            (None, _) => {
+                return Some(Restricted {
+                    content,
+                    next: unproducible,
+                })
+            }
+        };
+
+        content.push(c);
+        known.push(c);
+    }
+
+    Some(Restricted {
+        content,
+        next: Vec::new(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::oracle::{EncryptionOracle, QueryCounter};
 
     #[test]
     fn challenge() {
@@ -122,4 +274,120 @@ Did you stop? No, I just drove by
             assert_eq!(attack_len(&oracle), (l, 16));
         }
     }
+
+    #[test]
+    fn dict() {
+        let content = b"Rollin' in my 5.0
+With my rag-top down so my hair can blow
+The girlies on standby waving just to say hi
+Did you stop? No, I just drove by
+";
+        let oracle = Oracle::new(content);
+
+        let slow = QueryCounter::new(&oracle);
+        assert_eq!(attack(&slow), content);
+
+        let fast = QueryCounter::new(&oracle);
+        assert_eq!(attack_dict(&fast).unwrap(), content);
+
+        // attack() stops at the right value, so on ASCII text it takes
+        // about 90 queries per byte rather than 256, while attack_dict()
+        // takes one, plus a few dozen to get started: that's less than
+        // 100 times fewer here, but more than that against the worst case.
+        assert!(fast.count() * 50 < slow.count());
+        assert!(fast.count() * 100 < content.len() * 257);
+    }
+
+    // Same content, different key every time
+    struct FickleOracle(Vec<u8>);
+
+    impl EncryptionOracle for FickleOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            Oracle::new(&self.0).encrypt(input)
+        }
+    }
+
+    impl DeterministicOracle for FickleOracle {}
+
+    #[test]
+    fn dict_not_deterministic() {
+        let oracle = FickleOracle(b"Rollin' in my 5.0".to_vec());
+        assert_eq!(attack_dict(&oracle), None);
+    }
+
+    // Like Oracle, but rejects '&', '=' and non-printable input (other than
+    // newlines), and escapes '"' and '\\' with a '\\'.
+    struct FilteringOracle(Oracle);
+
+    fn allowed(b: u8) -> bool {
+        (b.is_ascii_graphic() || b == b' ' || b == b'\n') && b != b'&' && b != b'='
+    }
+
+    fn escape(b: u8) -> Vec<u8> {
+        match b {
+            b'"' | b'\\' => vec![b'\\', b],
+            _ => vec![b],
+        }
+    }
+
+    impl EncryptionOracle for FilteringOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            if !input.iter().all(|&b| allowed(b)) {
+                return Vec::new();
+            }
+            let escaped: Vec<_> = input.iter().flat_map(|&b| escape(b)).collect();
+            self.0.encrypt(&escaped)
+        }
+    }
+
+    impl DeterministicOracle for FilteringOracle {}
+
+    #[test]
+    fn restricted() {
+        let content = b"Rollin' in my 5.0
+With my rag-top down so my hair can blow
+The girlies on standby waving just to say hi
+Did you stop? No, I just drove by
+";
+        let alphabet: Vec<_> = (0..=255).filter(|&b| allowed(b)).collect();
+        let text: Vec<_> = (0..=255)
+            .filter(|&b: &u8| b.is_ascii_graphic() || b == b' ' || b == b'\n')
+            .collect();
+        let oracle = FilteringOracle(Oracle::new(content));
+        let got = attack_restricted(&oracle, &alphabet, escape, &text).unwrap();
+        assert_eq!(got.content, content);
+        assert_eq!(got.next, b"");
+
+        // '"', '&', '=' and '\\' can't be produced from our input: we only
+        // know the next byte is one of them
+        for (content, known) in [(&b"say \"hi\""[..], &b"say "[..]), (b"role=admin", b"role")] {
+            let oracle = FilteringOracle(Oracle::new(content));
+            let got = attack_restricted(&oracle, &alphabet, escape, &text).unwrap();
+            assert_eq!(got.content, known);
+            assert_eq!(got.next, b"\"&=\\");
+        }
+    }
+
+    // With a tighter charset, '=' is the only byte left
+    #[test]
+    fn restricted_base64() {
+        let alphabet: Vec<_> = (0..=255).filter(|&b| allowed(b)).collect();
+        let base64 = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+        // Padding at the end of a token
+        let token = b"c2VjcmV0IHRva2VuISE=";
+        let oracle = FilteringOracle(Oracle::new(token));
+        let got = attack_restricted(&oracle, &alphabet, escape, base64).unwrap();
+        assert_eq!(got.content, token);
+        assert_eq!(got.next, b"");
+
+        // But nothing after it can be tested
+        let token = b"role=admin";
+        let oracle = FilteringOracle(Oracle::new(token));
+        let got = attack_restricted(&oracle, &alphabet, escape, base64).unwrap();
+        assert_eq!(got.content, b"role=");
+        let mut any = base64.to_vec();
+        any.sort();
+        assert_eq!(got.next, any);
+    }
 }
@@ -1,6 +1,72 @@
+// Encoding and parsing of k=v&k=v strings.
+//
+// '%', '&' and '=' in keys and values are escaped as %25, %26 and %3D.
+pub mod kv {
+    pub fn escape(s: &str) -> String {
+        s.replace('%', "%25")
+            .replace('&', "%26")
+            .replace('=', "%3D")
+    }
+
+    // Reject '&', '=' and invalid escapes
+    pub fn unescape(s: &str) -> Option<String> {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find(['%', '&', '=']) {
+            out.push_str(&rest[..i]);
+            let c = match rest.get(i..i + 3)? {
+                "%25" => '%',
+                "%26" => '&',
+                "%3D" => '=',
+                _ => return None,
+            };
+            out.push(c);
+            rest = &rest[i + 3..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+
+    pub fn encode(pairs: &[(&str, &str)]) -> String {
+        pairs
+            .iter()
+            .map(|(k, v)| format!("{}={}", escape(k), escape(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    pub fn parse(s: &str) -> Option<Vec<(String, String)>> {
+        s.split('&')
+            .map(|pair| {
+                let (k, v) = pair.split_once('=')?;
+                Some((unescape(k)?, unescape(v)?))
+            })
+            .collect()
+    }
+
+    // Value of the first field with that key
+    pub fn get<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// Use 0-padding - doesn't matter, just convenient
+pub fn zero_pad(raw: &[u8], block_size: usize) -> Vec<u8> {
+    let mut padded = raw.to_owned();
+    padded.resize(raw.len() / block_size * block_size + block_size, 0);
+    padded
+}
+
 // use a separate module for privacy
 mod oracle {
+    use super::{kv, zero_pad};
+    use crate::consttime::ct_eq;
+    use crate::oracle::{EncryptionOracle, ValidityOracle};
     use crate::s1c07::{aes_128_ecb_decrypt, aes_128_ecb_encrypt};
+    use crate::s2c09::{pkcs7_pad, pkcs7_unpad};
     use rand::{thread_rng, Rng};
 
     pub struct Oracle {
@@ -18,23 +84,14 @@ mod oracle {
 
         // Encode and encrypt profile for this email address
         pub fn encrypted_profile_for(&self, email: &str) -> Vec<u8> {
-            // Don't allow direct injection attacks
-            if email.contains("&") || email.contains("=") {
-                return Vec::new();
-            }
-
             // In real life we map email to UID, but it doesn't matter here
-            let uid = thread_rng().gen::<u8>();
+            let uid = format!("{:03}", thread_rng().gen::<u8>());
 
-            // Encode the user profile
-            let profile = format!("email={}&uid={:03}&role=user", email, uid);
+            // Encode the user profile - escaping prevents direct injection
+            let profile = kv::encode(&[("email", email), ("uid", &uid), ("role", "user")]);
             println!("profile to be encrypted: '{}'", profile);
 
-            // Use 0-padding - doesn't matter, just convenient
-            let mut clear = profile.as_bytes().to_owned();
-            let padded_size = clear.len() % 16 * 16 + 16;
-            clear.resize(padded_size, 0);
-
+            let clear = zero_pad(profile.as_bytes(), 16);
             aes_128_ecb_encrypt(&self.key, &clear).unwrap()
         }
 
@@ -51,25 +108,158 @@ mod oracle {
             let profile = padded.trim_end_matches('\0');
             println!("decrypted profile: '{}'", profile);
 
-            profile.ends_with("&role=admin")
+            let Some(fields) = kv::parse(profile) else {
+                return false;
+            };
+            kv::get(&fields, "role").is_some_and(|role| ct_eq(role.as_bytes(), b"admin"))
+        }
+    }
+
+    // The attacker-controlled input is the email address
+    impl EncryptionOracle for Oracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            match std::str::from_utf8(input) {
+                Ok(email) => self.encrypted_profile_for(email),
+                Err(_) => Vec::new(),
+            }
+        }
+    }
+
+    impl ValidityOracle for Oracle {
+        fn is_valid(&self, token: &[u8]) -> bool {
+            self.is_admin(token)
+        }
+    }
+
+    // Same with real PKCS#7 padding and a strict parser
+    pub struct StrictOracle {
+        key: [u8; 16],
+    }
+
+    impl StrictOracle {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            let mut key = [0u8; 16];
+            thread_rng().fill(&mut key[..]);
+
+            Self { key }
+        }
+
+        pub fn encrypted_profile_for(&self, email: &str) -> Vec<u8> {
+            let uid = format!("{:03}", thread_rng().gen::<u8>());
+            let profile = kv::encode(&[("email", email), ("uid", &uid), ("role", "user")]);
+            let clear = pkcs7_pad(profile.as_bytes(), 16);
+            aes_128_ecb_encrypt(&self.key, &clear).unwrap()
+        }
+
+        // Only accept properly padded profiles with exactly the expected
+        // fields, all well-formed
+        pub fn is_admin(&self, token: &[u8]) -> bool {
+            let Some(padded) = aes_128_ecb_decrypt(&self.key, token) else {
+                return false;
+            };
+            let Some(clear) = pkcs7_unpad(&padded, 16) else {
+                return false;
+            };
+            let Ok(profile) = String::from_utf8(clear) else {
+                return false;
+            };
+            let Some(fields) = kv::parse(&profile) else {
+                return false;
+            };
+
+            match &fields[..] {
+                [(k1, _), (k2, uid), (k3, role)] => {
+                    k1 == "email"
+                        && k2 == "uid"
+                        && uid.len() == 3
+                        && uid.bytes().all(|b| b.is_ascii_digit())
+                        && k3 == "role"
+                        && ct_eq(role.as_bytes(), b"admin")
+                }
+                _ => false,
+            }
         }
     }
+
+    impl EncryptionOracle for StrictOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            match std::str::from_utf8(input) {
+                Ok(email) => self.encrypted_profile_for(email),
+                Err(_) => Vec::new(),
+            }
+        }
+    }
+
+    impl ValidityOracle for StrictOracle {
+        fn is_valid(&self, token: &[u8]) -> bool {
+            self.is_admin(token)
+        }
+    }
+}
+
+use crate::analysis::detect_block_size;
+use crate::oracle::EncryptionOracle;
+use crate::s2c09::pkcs7_pad;
+pub use oracle::{Oracle, StrictOracle};
+
+// Layout of an encoded record: prefix || input || middle || value || padding.
+// Only the lengths of prefix and middle matter.
+pub struct Template<'a> {
+    pub prefix: &'a str,
+    pub middle: &'a str,
 }
 
-use oracle::Oracle;
+pub const PROFILE: Template = Template {
+    prefix: "email=",
+    middle: "&uid=000&role=",
+};
+
+// Forge a token where the field after middle has the given value.
+//
+// First, put the value, padded the same way as the oracle pads the full
+// record, at the start of a block in our input, and copy the blocks that
+// encrypt it. Then make our input just long enough that middle ends on a
+// block boundary, and paste the copied blocks right there.
+// This assumes the padded value goes through the oracle's encoding
+// unchanged.
+pub fn cut_and_paste<T, F>(victim: &T, template: &Template, value: &[u8], pad: F) -> Option<Vec<u8>>
+where
+    T: EncryptionOracle + ?Sized,
+    F: Fn(&[u8], usize) -> Vec<u8>,
+{
+    let block_size = detect_block_size(victim)?;
+    let fill_len = |len: usize| (block_size - len % block_size) % block_size;
+
+    let prefix_len = template.prefix.len();
+    let copy_fill = fill_len(prefix_len);
+    let padded_value = pad(value, block_size);
+    let mut input = vec![b'x'; copy_fill];
+    input.extend_from_slice(&padded_value);
+    let copy_start = prefix_len + copy_fill;
+    let copied = victim
+        .encrypt(&input)
+        .get(copy_start..copy_start + padded_value.len())?
+        .to_owned();
+
+    let paste_at = prefix_len + template.middle.len();
+    let paste_fill = fill_len(paste_at);
+    let mut token = victim.encrypt(&vec![b'x'; paste_fill]);
+    token.truncate(paste_at + paste_fill);
+    token.extend_from_slice(&copied);
+
+    Some(token)
+}
 
 // Forge a token (encrypted profile) with role=admin
-pub fn attack(victim: &Oracle) -> Vec<u8> {
-    // 0               1               2               3               4
-    // 0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef
-    // email=xxxxxxxxxxadmin00000000000@example.xxxxxxxxx&uid=123&role=user000000000000
-    //                 <---- copy ---->                                <---- paste --->
-    let email = "xxxxxxxxxxadmin\0\0\0\0\0\0\0\0\0\0\0@example.xxxxxxxxx";
-    let mut token = victim.encrypted_profile_for(email);
-    token.truncate(0x40);
-    token.extend_from_within(0x10..0x20);
-
-    token
+pub fn attack<T: EncryptionOracle + ?Sized>(victim: &T) -> Vec<u8> {
+    cut_and_paste(victim, &PROFILE, b"admin", zero_pad).unwrap()
+}
+
+// Same with PKCS#7 padding: the pasted block is "admin" followed by
+// eleven 0x0b bytes, which is exactly a valid last block.
+pub fn attack_pkcs7<T: EncryptionOracle + ?Sized>(victim: &T) -> Vec<u8> {
+    cut_and_paste(victim, &PROFILE, b"admin", pkcs7_pad).unwrap()
 }
 
 #[cfg(test)]
@@ -82,4 +272,86 @@ mod tests {
         let forged_token = attack(&victim);
         assert!(victim.is_admin(&forged_token));
     }
+
+    #[test]
+    fn validity_oracle() {
+        use crate::oracle::ValidityOracle;
+
+        let victim = Oracle::new();
+        let honest_token = victim.encrypted_profile_for("foo@bar.com");
+        assert!(!victim.is_valid(&honest_token));
+        assert!(victim.is_valid(&attack(&victim)));
+    }
+
+    #[test]
+    fn kv_codec() {
+        let encoded = kv::encode(&[("foo", "bar"), ("baz", "qux"), ("zap", "zazzle")]);
+        assert_eq!(encoded, "foo=bar&baz=qux&zap=zazzle");
+        let fields = kv::parse(&encoded).unwrap();
+        assert_eq!(kv::get(&fields, "baz"), Some("qux"));
+        assert_eq!(kv::get(&fields, "nope"), None);
+
+        let tricky = "foo@bar.com&role=admin%";
+        let encoded = kv::encode(&[("email", tricky), ("a=b", "")]);
+        assert_eq!(encoded, "email=foo@bar.com%26role%3Dadmin%25&a%3Db=");
+        let fields = kv::parse(&encoded).unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(kv::get(&fields, "email"), Some(tricky));
+        assert_eq!(kv::get(&fields, "a=b"), Some(""));
+
+        assert_eq!(kv::parse("foo"), None);
+        assert_eq!(kv::parse("foo=bar=baz"), None);
+        assert_eq!(kv::parse("foo=%41"), None);
+        assert_eq!(kv::parse("foo=%2"), None);
+    }
+
+    #[test]
+    fn no_injection() {
+        let victim = Oracle::new();
+        let token = victim.encrypted_profile_for("foo@bar.com&role=admin");
+        assert!(!victim.is_admin(&token));
+    }
+
+    // Different layout, different padding
+    struct OtherOracle {
+        key: [u8; 16],
+    }
+
+    impl EncryptionOracle for OtherOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            let name = String::from_utf8_lossy(input);
+            let record = kv::encode(&[("user", &name), ("shell", "/bin/sh"), ("admin", "no")]);
+            let clear = crate::s2c09::pkcs7_pad(record.as_bytes(), 16);
+            crate::s1c07::aes_128_ecb_encrypt(&self.key, &clear).unwrap()
+        }
+    }
+
+    #[test]
+    fn other_layout() {
+        let victim = OtherOracle {
+            key: rand::random(),
+        };
+        let template = Template {
+            prefix: "user=",
+            middle: "&shell=/bin/sh&admin=",
+        };
+        let pad = crate::s2c09::pkcs7_pad;
+        let token = cut_and_paste(&victim, &template, b"yes", pad).unwrap();
+
+        let clear = crate::s1c07::aes_128_ecb_decrypt(&victim.key, &token).unwrap();
+        let record = String::from_utf8(clear).unwrap();
+        assert!(record.ends_with("&admin=yes\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d\x0d"));
+    }
+
+    #[test]
+    fn strict() {
+        let victim = StrictOracle::new();
+        let honest_token = victim.encrypted_profile_for("foo@bar.com");
+        assert!(!victim.is_admin(&honest_token));
+
+        // The zero-padding attack doesn't cut it
+        assert!(!victim.is_admin(&attack(&victim)));
+
+        assert!(victim.is_admin(&attack_pkcs7(&victim)));
+    }
 }
@@ -1,5 +1,6 @@
 // use a separate module for privacy
 mod oracle {
+    use crate::oracle::{EncryptionOracle, RandomizedOracle};
     use crate::s1c07::aes_128_ecb_encrypt;
     use rand::{thread_rng, Rng};
 
@@ -17,8 +18,10 @@ mod oracle {
 
             Self { content, key }
         }
+    }
 
-        pub fn process(&self, input: &[u8]) -> Vec<u8> {
+    impl EncryptionOracle for Oracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
             let mut rng = thread_rng();
             // random-prefix || attacker-controlled || target-bytes
             let rp_len = rng.gen::<u8>();
@@ -37,14 +40,38 @@ mod oracle {
             aes_128_ecb_encrypt(&self.key, &clear).unwrap()
         }
     }
+
+    impl RandomizedOracle for Oracle {}
 }
 
-use oracle::Oracle;
+pub use oracle::Oracle;
 
-use crate::s2c12::attack as deterministic_attack;
-use crate::s2c12::DeterministicOracle;
+use crate::analysis::{detect_block_size, AnalysisError};
+use crate::oracle::{DeterministicOracle, EncryptionOracle, RandomizedOracle};
+use crate::s2c12::try_attack;
+use std::cell::Cell;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WrapperError {
+    // Output doesn't look like ECB with any block size we can detect
+    BlockSize,
+    // Couldn't get the encryption of the sentinel blocks
+    Sentinel,
+    // The sentinel didn't show up in the output after max_tries queries
+    TooManyTries,
+    // The wrapped oracle didn't behave as expected by the attack
+    Analysis(AnalysisError),
+}
+
+impl From<AnalysisError> for WrapperError {
+    fn from(err: AnalysisError) -> Self {
+        Self::Analysis(err)
+    }
+}
 
-// Wrap a non-deterministic Oracle into a deterministic one.
+pub const DEFAULT_MAX_TRIES: usize = 4096;
+
+// Wrap a non-deterministic ECB Oracle into a deterministic one.
 //
 // Get rid of the random part by using a sentinel consisting of the
 // encryption of two blocks of all-0 then all-1.
@@ -58,77 +85,98 @@ use crate::s2c12::DeterministicOracle;
 // It is of course possible that the random prefix contains two blocks
 // of all-0 then all-1 but that's extremely unlikely so we just ignore
 // that possibility.
-struct OracleWrapper<'a> {
-    oracle: &'a Oracle,
+//
+// The sentinel only shows up when it's aligned on a block boundary, so
+// we put 0 to block_size - 1 bytes of filler in front of it. With a
+// random-length prefix, any filler length works once in a while; with a
+// fixed-length prefix, only one works, but it always does, so remember
+// the last one that worked.
+pub struct OracleWrapper<'a, T: RandomizedOracle + ?Sized> {
+    oracle: &'a T,
+    block_size: usize,
     sentinel: Vec<u8>,
+    filler_len: Cell<usize>,
+    max_tries: usize,
 }
 
-fn gcd(a: usize, b: usize) -> usize {
-    if a == 0 {
-        b
-    } else if b == 0 {
-        a
-    } else if a >= b {
-        gcd(a - b, b)
-    } else {
-        gcd(b - a, a)
+impl<'a, T: RandomizedOracle + ?Sized> OracleWrapper<'a, T> {
+    pub fn new(oracle: &'a T) -> Result<Self, WrapperError> {
+        Self::with_max_tries(oracle, DEFAULT_MAX_TRIES)
     }
-}
 
-impl<'a> OracleWrapper<'a> {
-    fn new(oracle: &'a Oracle) -> Self {
-        // Guess the block size - this might overshoot but that's OK,
-        // we'll just be using a sentinel larger than necessary.
-        // (10 iterations means roughly 1 in 2^10 chance of getting an extra
-        // factor 2, also 1 in 3^10 of getting an extra factor 3, etc.)
-        let mut block_size = oracle.process(b"").len();
-        for _ in 1..10 {
-            block_size = gcd(block_size, oracle.process(b"").len());
-        }
+    pub fn with_max_tries(oracle: &'a T, max_tries: usize) -> Result<Self, WrapperError> {
+        let block_size = detect_block_size(oracle).ok_or(WrapperError::BlockSize)?;
 
         // Compute our sentinel, see the definition of OracleWrapper.
         let mut sentinel = Vec::new();
         for v in 0..=1u8 {
-            let blocks = vec![v; 3 * block_size];
-            let out = oracle.process(&blocks);
-            for i in (0..(out.len() - block_size)).step_by(block_size) {
-                let cur_block = &out[i..(i + block_size)];
-                let next_block = &out[(i + block_size)..(i + 2 * block_size)];
-                if cur_block == next_block {
-                    sentinel.extend_from_slice(cur_block);
-                    break;
-                }
-            }
+            let out = oracle.encrypt(&vec![v; 3 * block_size]);
+            let blocks: Vec<_> = out.chunks_exact(block_size).collect();
+            let pair = blocks
+                .windows(2)
+                .find(|pair| pair[0] == pair[1])
+                .ok_or(WrapperError::Sentinel)?;
+            sentinel.extend_from_slice(pair[0]);
         }
 
-        Self { oracle, sentinel }
+        Ok(Self {
+            oracle,
+            block_size,
+            sentinel,
+            filler_len: Cell::new(0),
+            max_tries,
+        })
     }
-}
 
-impl<'a> DeterministicOracle for OracleWrapper<'a> {
-    fn process(&self, input: &[u8]) -> Vec<u8> {
-        let block_size = self.sentinel.len() / 2;
-
-        let mut ext_input = Vec::new();
-        ext_input.extend_from_slice(&vec![0u8; block_size]);
-        ext_input.extend_from_slice(&vec![1u8; block_size]);
-        ext_input.extend_from_slice(input);
-
-        loop {
-            let out = self.oracle.process(&ext_input);
-            for i in (0..(out.len() - 2 * block_size)).step_by(block_size) {
-                if out[i..(i + 2 * block_size)] == self.sentinel {
-                    return out[i + 2 * block_size..].to_owned();
-                }
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn try_encrypt(&self, input: &[u8]) -> Result<Vec<u8>, WrapperError> {
+        let block_size = self.block_size;
+
+        for i in 0..self.max_tries {
+            let filler_len = (self.filler_len.get() + i) % block_size;
+
+            let mut ext_input = vec![0u8; filler_len + block_size];
+            ext_input.extend_from_slice(&vec![1u8; block_size]);
+            ext_input.extend_from_slice(input);
+
+            let out = self.oracle.encrypt(&ext_input);
+            let found = (0..out.len())
+                .step_by(block_size)
+                .find(|&i| out[i..].starts_with(&self.sentinel));
+            if let Some(start) = found {
+                self.filler_len.set(filler_len);
+                return Ok(out[start + self.sentinel.len()..].to_owned());
             }
         }
+
+        Err(WrapperError::TooManyTries)
     }
 }
 
+impl<T: RandomizedOracle + ?Sized> EncryptionOracle for OracleWrapper<'_, T> {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        self.try_encrypt(input)
+            .expect("sentinel not found, use try_encrypt() to handle this")
+    }
+}
+
+impl<T: RandomizedOracle + ?Sized> DeterministicOracle for OracleWrapper<'_, T> {}
+
 // Find the content hidden in the Oracle
 // victim.content is private and can't be read
-pub fn attack(victim: &Oracle) -> Vec<u8> {
-    deterministic_attack(&OracleWrapper::new(victim))
+pub fn attack<T: RandomizedOracle + ?Sized>(victim: &T) -> Result<Vec<u8>, WrapperError> {
+    attack_wrapped(&OracleWrapper::new(victim)?)
+}
+
+// Same as attack(), through a given wrapper: if a query runs out of
+// tries, return the error rather than panicking
+pub fn attack_wrapped<T: RandomizedOracle + ?Sized>(
+    wrapper: &OracleWrapper<T>,
+) -> Result<Vec<u8>, WrapperError> {
+    try_attack(|input| wrapper.try_encrypt(input))
 }
 
 #[cfg(test)]
@@ -144,14 +192,14 @@ The girlies on standby waving just to say hi
 Did you stop? No, I just drove by
 ";
         let oracle = Oracle::new(content);
-        assert_eq!(attack(&oracle), content);
+        assert_eq!(attack(&oracle), Ok(content.to_vec()));
     }
 
     #[test]
     fn oracle_wrapper() {
         let content = b"The quick brown fox jumps over the lazy dog.";
         let oracle = Oracle::new(content);
-        let oracle_wrap = OracleWrapper::new(&oracle);
+        let oracle_wrap = OracleWrapper::new(&oracle).unwrap();
         let oracle_ref = s2c12::Oracle::new(content);
 
         let input = [b'A'; 17];
@@ -159,7 +207,72 @@ Did you stop? No, I just drove by
             let i = &input[..l];
             // We can't compare ciphertexts because the two oracles will use
             // different (random) keys, but the length is a key indicator.
-            assert_eq!(oracle_wrap.process(i).len(), oracle_ref.process(i).len())
+            assert_eq!(oracle_wrap.encrypt(i).len(), oracle_ref.encrypt(i).len())
         }
     }
+
+    // ECB with a fixed-length random prefix
+    struct FixedPrefixOracle {
+        prefix: Vec<u8>,
+        content: Vec<u8>,
+        key: [u8; 16],
+    }
+
+    impl FixedPrefixOracle {
+        fn new(prefix_len: usize, content: &[u8]) -> Self {
+            Self {
+                prefix: (0..prefix_len).map(|_| rand::random()).collect(),
+                content: content.to_owned(),
+                key: rand::random(),
+            }
+        }
+    }
+
+    impl EncryptionOracle for FixedPrefixOracle {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            let mut clear = self.prefix.clone();
+            clear.extend_from_slice(input);
+            clear.extend_from_slice(&self.content);
+            let clear = crate::s2c09::pkcs7_pad(&clear, 16);
+            crate::s1c07::aes_128_ecb_encrypt(&self.key, &clear).unwrap()
+        }
+    }
+
+    impl RandomizedOracle for FixedPrefixOracle {}
+
+    #[test]
+    fn fixed_prefix() {
+        let content = b"The quick brown fox jumps over the lazy dog.";
+        for prefix_len in 0..=33 {
+            let oracle = FixedPrefixOracle::new(prefix_len, content);
+            // With a fixed prefix, the filler that works always does
+            let oracle_wrap = OracleWrapper::with_max_tries(&oracle, 16).unwrap();
+            assert_eq!(oracle_wrap.block_size(), 16);
+            assert_eq!(attack_wrapped(&oracle_wrap), Ok(content.to_vec()));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let oracle = crate::s2c11::Oracle::new(crate::s2c11::Mode::CBC);
+        assert_eq!(
+            OracleWrapper::new(&oracle).err(),
+            Some(WrapperError::BlockSize)
+        );
+
+        let oracle = Oracle::new(b"Some content");
+        let oracle_wrap = OracleWrapper::with_max_tries(&oracle, 0).unwrap();
+        assert_eq!(
+            oracle_wrap.try_encrypt(b""),
+            Err(WrapperError::TooManyTries)
+        );
+
+        // With a random-length prefix, a single try only works one time in
+        // 16, and the attack needs hundreds of queries
+        let oracle_wrap = OracleWrapper::with_max_tries(&oracle, 1).unwrap();
+        assert_eq!(
+            attack_wrapped(&oracle_wrap),
+            Err(WrapperError::TooManyTries)
+        );
+    }
 }
@@ -0,0 +1,39 @@
+use crate::s1c07::aes_128_ecb_encrypt;
+use std::iter::zip;
+
+// Keystream block i is the encryption of nonce || i, both 64-bit
+// little-endian. Encryption and decryption are the same.
+pub fn aes_128_ctr(key: &[u8], nonce: u64, data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let mut counter_block = nonce.to_le_bytes().to_vec();
+        counter_block.extend_from_slice(&(i as u64).to_le_bytes());
+        let keystream = aes_128_ecb_encrypt(key, &counter_block)?;
+        out.extend(zip(chunk, keystream).map(|(x, y)| x ^ y));
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::*;
+
+    #[test]
+    fn challenge() {
+        let b64 = "L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==";
+        let ct = BASE64_STANDARD.decode(b64).unwrap();
+        let key = b"YELLOW SUBMARINE";
+        let exp = b"Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ";
+
+        assert_eq!(aes_128_ctr(key, 0, &ct).unwrap(), exp);
+        assert_eq!(aes_128_ctr(key, 0, exp).unwrap(), ct);
+    }
+
+    #[test]
+    fn bad_key() {
+        assert_eq!(aes_128_ctr(b"WHITE SUBMARINE", 0, b"foo"), None);
+        assert_eq!(aes_128_ctr(b"YELLOW SUBMARINE", 0, b""), Some(vec![]));
+    }
+}
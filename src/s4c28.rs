@@ -0,0 +1,273 @@
+use std::fmt::Debug;
+
+// What differs between the Merkle-Damgård hashes here (SHA-1, MD4): the
+// initial state, the compression function, and the byte order used for
+// the digest and the length in the padding. Both use 64-byte blocks.
+pub trait MdCore: Clone {
+    type State: AsRef<[u32]> + AsMut<[u32]> + Default + Copy;
+    type Digest: AsRef<[u8]> + AsMut<[u8]> + Default + Copy + PartialEq + Debug;
+
+    const H0: Self::State;
+    const BIG_ENDIAN: bool;
+
+    fn compress(state: &mut Self::State, block: &[u8]);
+}
+
+// The buffering and padding common to all MdCores.
+//
+// The internal state is exposed: from_state() resumes hashing from a
+// digest, as if the message that produced it (including its padding)
+// had just been processed.
+#[derive(Clone)]
+pub struct MdHasher<C: MdCore> {
+    h: C::State,
+    // bytes not yet processed (less than a block)
+    buf: Vec<u8>,
+    // total number of bytes processed or in buf
+    len: u64,
+}
+
+pub const BLOCK_SIZE: usize = 64;
+
+impl<C: MdCore> Default for MdHasher<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: MdCore> MdHasher<C> {
+    pub fn new() -> Self {
+        Self {
+            h: C::H0,
+            buf: Vec::with_capacity(BLOCK_SIZE),
+            len: 0,
+        }
+    }
+
+    // len is the number of bytes that went into digest, including padding,
+    // so it must be a multiple of the block size.
+    pub fn from_state(digest: &C::Digest, len: u64) -> Self {
+        assert!(len.is_multiple_of(BLOCK_SIZE as u64));
+
+        let mut h = C::State::default();
+        for (hi, chunk) in h.as_mut().iter_mut().zip(digest.as_ref().chunks_exact(4)) {
+            let chunk = chunk.try_into().unwrap();
+            *hi = match C::BIG_ENDIAN {
+                true => u32::from_be_bytes(chunk),
+                false => u32::from_le_bytes(chunk),
+            };
+        }
+        Self {
+            h,
+            buf: Vec::with_capacity(BLOCK_SIZE),
+            len,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.len += data.len() as u64;
+
+        let mut data = data;
+        if !self.buf.is_empty() {
+            let take = data.len().min(BLOCK_SIZE - self.buf.len());
+            self.buf.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buf.len() < BLOCK_SIZE {
+                return;
+            }
+            let block = std::mem::take(&mut self.buf);
+            C::compress(&mut self.h, &block);
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            C::compress(&mut self.h, block);
+        }
+        self.buf.extend_from_slice(blocks.remainder());
+    }
+
+    pub fn finalize(mut self) -> C::Digest {
+        let padding = Self::padding(self.len);
+        self.update(&padding);
+        assert!(self.buf.is_empty());
+
+        let mut digest = C::Digest::default();
+        for (chunk, hi) in digest.as_mut().chunks_exact_mut(4).zip(self.h.as_ref()) {
+            chunk.copy_from_slice(&match C::BIG_ENDIAN {
+                true => hi.to_be_bytes(),
+                false => hi.to_le_bytes(),
+            });
+        }
+        digest
+    }
+
+    // The padding ("glue") that gets appended to a message of msg_len
+    // bytes: 0x80, then 0s, then the length in bits as a 64-bit integer.
+    pub fn padding(msg_len: u64) -> Vec<u8> {
+        let zeros = (BLOCK_SIZE as u64 * 2 - 9 - msg_len % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+        let mut pad = vec![0x80];
+        pad.resize(1 + zeros as usize, 0);
+        pad.extend_from_slice(&match C::BIG_ENDIAN {
+            true => (msg_len * 8).to_be_bytes(),
+            false => (msg_len * 8).to_le_bytes(),
+        });
+        pad
+    }
+
+    pub fn digest(data: &[u8]) -> C::Digest {
+        let mut h = Self::new();
+        h.update(data);
+        h.finalize()
+    }
+
+    // Secret-prefix MAC - don't do this, see challenge 29
+    pub fn mac(key: &[u8], msg: &[u8]) -> C::Digest {
+        let mut h = Self::new();
+        h.update(key);
+        h.update(msg);
+        h.finalize()
+    }
+}
+
+// SHA-1, see RFC 3174.
+#[derive(Clone)]
+pub struct Sha1Core;
+
+pub type Sha1 = MdHasher<Sha1Core>;
+
+pub const DIGEST_SIZE: usize = 20;
+
+impl MdCore for Sha1Core {
+    type State = [u32; 5];
+    type Digest = [u8; DIGEST_SIZE];
+
+    const H0: Self::State = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    const BIG_ENDIAN: bool = true;
+
+    fn compress(state: &mut Self::State, block: &[u8]) {
+        let mut w = [0u32; 80];
+        for (wi, chunk) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *wi = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for t in 16..80 {
+            w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = *state;
+        for (t, wt) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wt);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (hi, x) in state.iter_mut().zip([a, b, c, d, e]) {
+            *hi = hi.wrapping_add(x);
+        }
+    }
+}
+
+pub fn sha1(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    Sha1::digest(data)
+}
+
+pub fn sha1_mac(key: &[u8], msg: &[u8]) -> [u8; DIGEST_SIZE] {
+    Sha1::mac(key, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex;
+
+    #[test]
+    fn vectors() {
+        let vectors: [(&[u8], &str); 3] = [
+            (b"", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+            (b"abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+            ),
+        ];
+        for (msg, exp) in vectors {
+            assert_eq!(hex::encode(sha1(msg)), exp);
+        }
+
+        let million_a = vec![b'a'; 1_000_000];
+        let exp = "34aa973cd4c4daa4f61eeb2bdbad27316534016f";
+        assert_eq!(hex::encode(sha1(&million_a)), exp);
+    }
+
+    #[test]
+    fn incremental() {
+        let data: Vec<u8> = (0..=255).collect();
+        let exp = sha1(&data);
+        for split in [0, 1, 55, 56, 63, 64, 65, 128, 200] {
+            let mut h = Sha1::new();
+            h.update(&data[..split]);
+            h.update(&data[split..]);
+            assert_eq!(h.finalize(), exp);
+        }
+    }
+
+    #[test]
+    fn padding_len() {
+        for len in 0..200 {
+            assert_eq!(
+                (len + Sha1::padding(len).len() as u64) % BLOCK_SIZE as u64,
+                0
+            );
+        }
+        assert_eq!(Sha1::padding(55).len(), 9);
+        assert_eq!(Sha1::padding(56).len(), 72);
+    }
+
+    fn resume<C: MdCore>() {
+        let msg = b"The quick brown fox jumps over the lazy dog";
+        let ext = b" and then some";
+        let mut glued = msg.to_vec();
+        glued.extend_from_slice(&MdHasher::<C>::padding(msg.len() as u64));
+        let glued_len = glued.len() as u64;
+        glued.extend_from_slice(ext);
+
+        let mut h = MdHasher::<C>::from_state(&MdHasher::<C>::digest(msg), glued_len);
+        h.update(ext);
+        assert_eq!(h.finalize(), MdHasher::<C>::digest(&glued));
+    }
+
+    #[test]
+    fn resume_sha1() {
+        resume::<Sha1Core>();
+    }
+
+    #[test]
+    fn resume_md4() {
+        resume::<crate::s4c30::Md4Core>();
+    }
+
+    #[test]
+    fn challenge() {
+        let key = b"YELLOW SUBMARINE";
+        let msg = b"Cooking MC's like a pound of bacon";
+        let mac = sha1_mac(key, msg);
+        assert_eq!(mac, sha1(&[&key[..], &msg[..]].concat()));
+
+        // Can't change the message or produce a MAC without the key
+        assert_ne!(sha1_mac(key, b"Cooking MC's like a pound of bacoN"), mac);
+        assert_ne!(sha1_mac(b"", msg), mac);
+    }
+}
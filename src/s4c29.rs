@@ -0,0 +1,133 @@
+use crate::oracle::ValidityOracle;
+use crate::s4c28::{MdCore, MdHasher};
+use std::ops::RangeInclusive;
+
+// use a separate module for privacy
+mod oracle {
+    use crate::consttime::ct_eq;
+    use crate::oracle::ValidityOracle;
+    use crate::s4c28::{MdCore, MdHasher, Sha1Core};
+    use rand::{thread_rng, Rng};
+    use std::marker::PhantomData;
+
+    // Secret-prefix MAC with any of our Merkle-Damgård hashes, SHA-1 by
+    // default
+    pub struct Oracle<C: MdCore = Sha1Core> {
+        key: Vec<u8>,
+        hash: PhantomData<C>,
+    }
+
+    impl<C: MdCore> Oracle<C> {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            let mut rng = thread_rng();
+            let key_len = rng.gen_range(8..=32);
+            let key = (0..key_len).map(|_| rng.gen()).collect();
+
+            Self {
+                key,
+                hash: PhantomData,
+            }
+        }
+
+        pub fn sign(&self, msg: &[u8]) -> C::Digest {
+            MdHasher::<C>::mac(&self.key, msg)
+        }
+
+        pub fn verify(&self, msg: &[u8], mac: &[u8]) -> bool {
+            ct_eq(self.sign(msg).as_ref(), mac)
+        }
+
+        pub fn is_admin(&self, msg: &[u8], mac: &[u8]) -> bool {
+            self.verify(msg, mac) && msg.windows(11).any(|w| w == b";admin=true")
+        }
+    }
+
+    // Token is message || MAC
+    impl<C: MdCore> ValidityOracle for Oracle<C> {
+        fn is_valid(&self, token: &[u8]) -> bool {
+            let digest_size = std::mem::size_of::<C::Digest>();
+            match token.len().checked_sub(digest_size) {
+                Some(msg_len) => self.verify(&token[..msg_len], &token[msg_len..]),
+                None => false,
+            }
+        }
+    }
+}
+
+pub use oracle::Oracle;
+
+// Given a message and its MAC, forge a MAC for message || glue || extension
+// where glue is the padding of key || message, for the hash given by C.
+//
+// We don't know the key length, so try each length in key_lens in turn
+// until the victim accepts the forged message.
+pub fn forge<C: MdCore, T: ValidityOracle + ?Sized>(
+    victim: &T,
+    msg: &[u8],
+    mac: &C::Digest,
+    extension: &[u8],
+    key_lens: RangeInclusive<usize>,
+) -> Option<(Vec<u8>, C::Digest)> {
+    for key_len in key_lens {
+        let glue = MdHasher::<C>::padding((key_len + msg.len()) as u64);
+        let glued_len = (key_len + msg.len() + glue.len()) as u64;
+
+        let mut h = MdHasher::<C>::from_state(mac, glued_len);
+        h.update(extension);
+        let forged_mac = h.finalize();
+
+        let mut forged_msg = msg.to_vec();
+        forged_msg.extend_from_slice(&glue);
+        forged_msg.extend_from_slice(extension);
+
+        let mut token = forged_msg.clone();
+        token.extend_from_slice(forged_mac.as_ref());
+        if victim.is_valid(&token) {
+            return Some((forged_msg, forged_mac));
+        }
+    }
+
+    None
+}
+
+pub fn attack<C: MdCore, T: ValidityOracle + ?Sized>(
+    victim: &T,
+    msg: &[u8],
+    mac: &C::Digest,
+) -> Option<(Vec<u8>, C::Digest)> {
+    forge::<C, T>(victim, msg, mac, b";admin=true", 0..=64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s4c28::Sha1Core;
+    use crate::s4c30::Md4Core;
+
+    fn challenge<C: MdCore>() {
+        let victim = Oracle::<C>::new();
+        let msg = b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+        let mac = victim.sign(msg);
+        assert!(!victim.is_admin(msg, mac.as_ref()));
+
+        let (forged_msg, forged_mac) = attack::<C, _>(&victim, msg, &mac).unwrap();
+        assert!(forged_msg.starts_with(msg));
+        assert!(forged_msg.ends_with(b";admin=true"));
+        assert!(victim.is_admin(&forged_msg, forged_mac.as_ref()));
+
+        // The key is at least 8 bytes long
+        let forged = forge::<C, _>(&victim, msg, &mac, b";admin=true", 0..=7);
+        assert_eq!(forged, None);
+    }
+
+    #[test]
+    fn challenge_sha1() {
+        challenge::<Sha1Core>();
+    }
+
+    #[test]
+    fn challenge_md4() {
+        challenge::<Md4Core>();
+    }
+}
@@ -0,0 +1,112 @@
+use crate::s4c28::{MdCore, MdHasher};
+
+// MD4, see RFC 1320.
+//
+// Same structure as SHA-1, so it shares s4c28::MdHasher, including
+// from_state() to resume hashing from a digest, but little-endian
+// everywhere.
+#[derive(Clone)]
+pub struct Md4Core;
+
+pub type Md4 = MdHasher<Md4Core>;
+
+pub const BLOCK_SIZE: usize = crate::s4c28::BLOCK_SIZE;
+pub const DIGEST_SIZE: usize = 16;
+
+impl MdCore for Md4Core {
+    type State = [u32; 4];
+    type Digest = [u8; DIGEST_SIZE];
+
+    const H0: Self::State = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476];
+    const BIG_ENDIAN: bool = false;
+
+    fn compress(state: &mut Self::State, block: &[u8]) {
+        let mut x = [0u32; 16];
+        for (xi, chunk) in x.iter_mut().zip(block.chunks_exact(4)) {
+            *xi = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+        let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+        let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+        // a = (a + f(b, c, d) + x + k) <<< s
+        let step = |a: u32, f: u32, x: u32, k: u32, s: u32| {
+            a.wrapping_add(f)
+                .wrapping_add(x)
+                .wrapping_add(k)
+                .rotate_left(s)
+        };
+
+        let [mut a, mut b, mut c, mut d] = *state;
+
+        for k in [0, 4, 8, 12] {
+            a = step(a, f(b, c, d), x[k], 0, 3);
+            d = step(d, f(a, b, c), x[k + 1], 0, 7);
+            c = step(c, f(d, a, b), x[k + 2], 0, 11);
+            b = step(b, f(c, d, a), x[k + 3], 0, 19);
+        }
+
+        for k in [0, 1, 2, 3] {
+            a = step(a, g(b, c, d), x[k], 0x5A827999, 3);
+            d = step(d, g(a, b, c), x[k + 4], 0x5A827999, 5);
+            c = step(c, g(d, a, b), x[k + 8], 0x5A827999, 9);
+            b = step(b, g(c, d, a), x[k + 12], 0x5A827999, 13);
+        }
+
+        for k in [0, 2, 1, 3] {
+            a = step(a, h(b, c, d), x[k], 0x6ED9EBA1, 3);
+            d = step(d, h(a, b, c), x[k + 8], 0x6ED9EBA1, 9);
+            c = step(c, h(d, a, b), x[k + 4], 0x6ED9EBA1, 11);
+            b = step(b, h(c, d, a), x[k + 12], 0x6ED9EBA1, 15);
+        }
+
+        for (hi, v) in state.iter_mut().zip([a, b, c, d]) {
+            *hi = hi.wrapping_add(v);
+        }
+    }
+}
+
+pub fn md4(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    Md4::digest(data)
+}
+
+// Secret-prefix MAC - as broken as with SHA-1: s4c29::attack::<Md4Core, _>()
+// forges it just the same, see the tests there
+pub fn md4_mac(key: &[u8], msg: &[u8]) -> [u8; DIGEST_SIZE] {
+    Md4::mac(key, msg)
+}
+
+// The s4c29 oracle, with MD4
+pub type Oracle = crate::s4c29::Oracle<Md4Core>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex;
+
+    #[test]
+    fn vectors() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            (b"a", "bde52cb31de33e46245e05fbdbd6fb24"),
+            (b"abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            (b"message digest", "d9130a8164549fe818874806e1c7014b"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                "d79e1c308aa5bbcdeea8ed63df412da9",
+            ),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "043f8582f241db351ce627e153e7f0e4",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+            ),
+        ];
+        for (msg, exp) in vectors {
+            assert_eq!(hex::encode(md4(msg)), exp);
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use crate::s2c13::kv;
+use crate::s4c28::{self, MdCore, MdHasher, Sha1};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// What HMAC needs to know about a hash function
+pub trait Hash {
+    const BLOCK_SIZE: usize;
+    const DIGEST_SIZE: usize;
+    fn digest(data: &[u8]) -> Vec<u8>;
+}
+
+impl<C: MdCore> Hash for MdHasher<C> {
+    const BLOCK_SIZE: usize = s4c28::BLOCK_SIZE;
+    const DIGEST_SIZE: usize = std::mem::size_of::<C::Digest>();
+    fn digest(data: &[u8]) -> Vec<u8> {
+        Self::digest(data).as_ref().to_vec()
+    }
+}
+
+// RFC 2104
+pub fn hmac<H: Hash>(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut block_key = match key.len() > H::BLOCK_SIZE {
+        true => H::digest(key),
+        false => key.to_vec(),
+    };
+    block_key.resize(H::BLOCK_SIZE, 0);
+
+    let mut inner: Vec<u8> = block_key.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(msg);
+    let mut outer: Vec<u8> = block_key.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&H::digest(&inner));
+
+    H::digest(&outer)
+}
+
+pub fn hmac_sha1(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    hmac::<Sha1>(key, msg)
+}
+
+// Compare byte by byte and stop at the first difference: whether a and b
+// are equal, and how many bytes matched before we stopped.
+fn short_circuit_compare(a: &[u8], b: &[u8]) -> (bool, usize) {
+    if a.len() != b.len() {
+        return (false, 0);
+    }
+    match std::iter::zip(a, b).position(|(x, y)| x != y) {
+        Some(i) => (false, i),
+        None => (true, a.len()),
+    }
+}
+
+// Compare byte by byte, stop at the first difference,
+// and sleep after each byte for good measure.
+pub fn insecure_compare(a: &[u8], b: &[u8], delay: Duration) -> bool {
+    let (equal, matched) = short_circuit_compare(a, b);
+    thread::sleep(delay * matched as u32);
+    equal
+}
+
+// Our view of the server: whether a signature is good for a file, and how
+// long it took to tell.
+pub trait TimingOracle {
+    fn check(&self, file: &str, signature: &[u8]) -> io::Result<(bool, Duration)>;
+}
+
+// How long reading a request may take, so that a client that stalls
+// doesn't hold up the others (or the server shutting down)
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A web server that checks HMAC-SHA1 signatures of files with
+// insecure_compare(), listening on loopback.
+//
+// Requests look like
+// GET /test?file=foo&signature=46b4ec586117154dacd49d664e5d63fdc88efb51
+// and the response is 200 if the signature is good, 500 otherwise.
+//
+// To keep things quick, the signature can be truncated to mac_len bytes,
+// between 1 and the full 20.
+pub struct Server {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    pub fn start(key: &[u8], delay: Duration, mac_len: usize) -> io::Result<Self> {
+        check_mac_len(mac_len, s4c28::DIGEST_SIZE)?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let key = key.to_vec();
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    // A misbehaving client shouldn't take the server down
+                    let _ = handle_request(stream, &key, delay, mac_len);
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // wake up the server thread so that it sees the flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl TimingOracle for Server {
+    fn check(&self, file: &str, signature: &[u8]) -> io::Result<(bool, Duration)> {
+        request(self.addr, file, signature)
+    }
+}
+
+// Same checks as the server, in process, with simulated time: each byte
+// that matches adds delay, and each request a random amount of noise up
+// to jitter, from a seeded generator. No sleeping, and the same results
+// every time for a given seed.
+pub struct SimulatedServer {
+    key: Vec<u8>,
+    delay: Duration,
+    mac_len: usize,
+    jitter: Duration,
+    rng: RefCell<StdRng>,
+}
+
+impl SimulatedServer {
+    pub fn new(
+        key: &[u8],
+        delay: Duration,
+        mac_len: usize,
+        jitter: Duration,
+        seed: u64,
+    ) -> io::Result<Self> {
+        check_mac_len(mac_len, s4c28::DIGEST_SIZE)?;
+        Ok(Self {
+            key: key.to_vec(),
+            delay,
+            mac_len,
+            jitter,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        })
+    }
+}
+
+impl TimingOracle for SimulatedServer {
+    fn check(&self, file: &str, signature: &[u8]) -> io::Result<(bool, Duration)> {
+        let expected = hmac_sha1(&self.key, file.as_bytes());
+        let (equal, matched) = short_circuit_compare(signature, &expected[..self.mac_len]);
+        let noise = self
+            .rng
+            .borrow_mut()
+            .gen_range(Duration::ZERO..=self.jitter);
+        Ok((equal, self.delay * matched as u32 + noise))
+    }
+}
+
+// Percent-encode everything but unreserved characters (RFC 3986)
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn url_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            _ => out.push(b),
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn check_request(line: &str, key: &[u8], delay: Duration, mac_len: usize) -> Option<bool> {
+    let target = line.strip_prefix("GET ")?.split(' ').next()?;
+    let query = target.strip_prefix("/test?")?;
+    let fields = query
+        .split('&')
+        .map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            Some((url_decode(k)?, url_decode(v)?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let file = kv::get(&fields, "file")?;
+    let signature = hex::decode(kv::get(&fields, "signature")?).ok()?;
+
+    let expected = hmac_sha1(key, file.as_bytes());
+    Some(insecure_compare(
+        &signature,
+        expected.get(..mac_len)?,
+        delay,
+    ))
+}
+
+fn handle_request(
+    stream: TcpStream,
+    key: &[u8],
+    delay: Duration,
+    mac_len: usize,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let status = match check_request(line.trim_end(), key, delay, mac_len) {
+        Some(true) => "200 OK",
+        _ => "500 Internal Server Error",
+    };
+    write!(&stream, "HTTP/1.0 {}\r\n\r\n", status)
+}
+
+pub(crate) fn check_mac_len(mac_len: usize, max: usize) -> io::Result<()> {
+    match (1..=max).contains(&mac_len) {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("MAC length must be between 1 and {}", max),
+        )),
+    }
+}
+
+// Send one request, tell if the signature was accepted and how long it took
+pub fn request(addr: SocketAddr, file: &str, signature: &[u8]) -> io::Result<(bool, Duration)> {
+    let start = Instant::now();
+    let mut stream = TcpStream::connect(addr)?;
+    write!(
+        stream,
+        "GET /test?file={}&signature={} HTTP/1.0\r\n\r\n",
+        url_encode(file),
+        hex::encode(signature)
+    )?;
+    let mut status = String::new();
+    BufReader::new(&stream).read_line(&mut status)?;
+    let elapsed = start.elapsed();
+
+    Ok((status.starts_with("HTTP/1.0 200"), elapsed))
+}
+
+// Find the signature for file, one byte at a time: the right value is the
+// one for which the server takes longest to say no, as it goes on to
+// compare the next byte. For the last byte, just see which one works.
+pub fn attack<T: TimingOracle + ?Sized>(
+    victim: &T,
+    file: &str,
+    mac_len: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    check_mac_len(mac_len, s4c28::DIGEST_SIZE)?;
+    let mut mac = vec![0u8; mac_len];
+    for i in 0..mac_len - 1 {
+        let mut slowest = (Duration::ZERO, 0);
+        for b in 0..=255 {
+            mac[i] = b;
+            let (_, elapsed) = victim.check(file, &mac)?;
+            slowest = slowest.max((elapsed, b));
+        }
+        mac[i] = slowest.1;
+    }
+
+    for b in 0..=255 {
+        mac[mac_len - 1] = b;
+        if victim.check(file, &mac)?.0 {
+            return Ok(Some(mac));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s4c30::{self, Md4};
+
+    #[test]
+    fn hmac_sha1_vectors() {
+        // RFC 2202
+        let vectors: [(&[u8], &[u8], &str); 3] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b617318655057264e28bc0b6fb378c8ef146be00",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+            ),
+            (
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "aa4ae5e15272d00e95705637ce8a3b55ed402112",
+            ),
+        ];
+        for (key, msg, exp) in vectors {
+            assert_eq!(hex::encode(hmac_sha1(key, msg)), exp);
+        }
+    }
+
+    #[test]
+    fn hmac_md4() {
+        let mac = hmac::<Md4>(b"key", b"msg");
+        assert_eq!(mac.len(), s4c30::DIGEST_SIZE);
+        assert_ne!(mac, hmac::<Md4>(b"kez", b"msg"));
+        assert_ne!(mac, hmac::<Md4>(b"key", b"msh"));
+    }
+
+    #[test]
+    fn server() {
+        let key = b"YELLOW SUBMARINE";
+        let server = Server::start(key, Duration::ZERO, 20).unwrap();
+        let good = hmac_sha1(key, b"foo");
+        let mut bad = good.clone();
+        bad[19] ^= 1;
+
+        assert!(request(server.addr(), "foo", &good).unwrap().0);
+        assert!(!request(server.addr(), "foo", &bad).unwrap().0);
+        assert!(!request(server.addr(), "bar", &good).unwrap().0);
+        assert!(!request(server.addr(), "foo", &good[..19]).unwrap().0);
+
+        // Escaped in the URL
+        for file in ["a b&signature=00", "100%", "é/?#"] {
+            let good = hmac_sha1(key, file.as_bytes());
+            assert!(request(server.addr(), file, &good).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn stalled_client() {
+        let server = Server::start(b"YELLOW SUBMARINE", Duration::ZERO, 20).unwrap();
+        // Connect and send nothing: the server gives up on us after a
+        // while and serves the next client, and can still be shut down
+        let _stalled = TcpStream::connect(server.addr()).unwrap();
+        let (ok, elapsed) = request(server.addr(), "foo", &[0; 20]).unwrap();
+        assert!(!ok);
+        assert!(elapsed >= READ_TIMEOUT / 2);
+        drop(server);
+    }
+
+    #[test]
+    fn bad_mac_len() {
+        let key = b"YELLOW SUBMARINE";
+        for mac_len in [0, 21] {
+            let err = Server::start(key, Duration::ZERO, mac_len).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        for mac_len in [0, 21] {
+            let sim = SimulatedServer::new(key, Duration::ZERO, mac_len, Duration::ZERO, 0);
+            assert_eq!(sim.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        }
+
+        let server = Server::start(key, Duration::ZERO, 20).unwrap();
+        for mac_len in [0, 21] {
+            let err = attack(&server, "foo", mac_len).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    // The attack itself, offline: the full 20 bytes, with simulated time
+    #[test]
+    fn simulated() {
+        let key: [u8; 16] = rand::random();
+        let delay = Duration::from_millis(50);
+        let victim = SimulatedServer::new(&key, delay, 20, Duration::ZERO, 31).unwrap();
+
+        let found = attack(&victim, "foo", 20).unwrap();
+        assert_eq!(found, Some(hmac_sha1(&key, b"foo")));
+    }
+
+    // Same over loopback, with wall-clock timing: a hiccup from the
+    // scheduler, say with other tests running in parallel, can throw it
+    // off. Run it on its own with --ignored.
+    #[test]
+    #[ignore]
+    fn challenge() {
+        let key: [u8; 16] = rand::random();
+        let mac_len = 3;
+        let server = Server::start(&key, Duration::from_millis(3), mac_len).unwrap();
+
+        let found = attack(&server, "foo", mac_len).unwrap();
+        assert_eq!(found, Some(hmac_sha1(&key, b"foo")[..mac_len].to_vec()));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::s4c28;
+use crate::s4c31::{check_mac_len, TimingOracle};
+use std::io;
+use std::time::Duration;
+
+// Find the value of byte i with the smallest possible delay: time each
+// candidate several times, interleaving candidates so that bursts of noise
+// hit all of them, and keep the median for each.
+fn guess_byte<T: TimingOracle + ?Sized>(
+    victim: &T,
+    file: &str,
+    mac: &mut [u8],
+    i: usize,
+    rounds: usize,
+) -> io::Result<u8> {
+    let mut timings: Vec<Vec<Duration>> = (0..256).map(|_| Vec::with_capacity(rounds)).collect();
+    for _ in 0..rounds {
+        for b in 0..=255 {
+            mac[i] = b;
+            let (_, elapsed) = victim.check(file, mac)?;
+            timings[b as usize].push(elapsed);
+        }
+    }
+
+    let median = |t: &mut Vec<Duration>| {
+        t.sort();
+        t[t.len() / 2]
+    };
+    let best = (0..=255u8)
+        .max_by_key(|&b| median(&mut timings[b as usize]))
+        .unwrap();
+    Ok(best)
+}
+
+// Same as s4c31::attack() but with statistics, so that it works with
+// much smaller delays.
+//
+// If we get a byte wrong anyway, we'll notice at the end because no value
+// of the last byte works: then start again with more rounds, up to
+// max_rounds. rounds must be at least 1.
+pub fn attack<T: TimingOracle + ?Sized>(
+    victim: &T,
+    file: &str,
+    mac_len: usize,
+    mut rounds: usize,
+    max_rounds: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    check_mac_len(mac_len, s4c28::DIGEST_SIZE)?;
+    if rounds == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "at least one round is needed",
+        ));
+    }
+
+    while rounds <= max_rounds {
+        let mut mac = vec![0u8; mac_len];
+        for i in 0..mac_len - 1 {
+            mac[i] = guess_byte(victim, file, &mut mac, i, rounds)?;
+        }
+
+        for b in 0..=255 {
+            mac[mac_len - 1] = b;
+            if victim.check(file, &mac)?.0 {
+                return Ok(Some(mac));
+            }
+        }
+
+        rounds *= 2;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s4c31;
+    use crate::s4c31::{hmac_sha1, Server, SimulatedServer};
+
+    #[test]
+    fn bad_args() {
+        let server = Server::start(b"YELLOW SUBMARINE", Duration::ZERO, 20).unwrap();
+        for (mac_len, rounds) in [(0, 5), (21, 5), (3, 0)] {
+            let err = attack(&server, "foo", mac_len, rounds, 20).err();
+            assert_eq!(err.unwrap().kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    // Offline, with simulated time: the noise on each request is several
+    // times the delay per byte, so a single sample per guess wouldn't do
+    #[test]
+    fn simulated() {
+        let key = b"YELLOW SUBMARINE";
+        let mac_len = 4;
+        let delay = Duration::from_micros(10);
+        let jitter = Duration::from_micros(20);
+        let victim = SimulatedServer::new(key, delay, mac_len, jitter, 32).unwrap();
+
+        let found = attack(&victim, "foo", mac_len, 16, 64).unwrap();
+        assert_eq!(found, Some(hmac_sha1(key, b"foo")[..mac_len].to_vec()));
+        assert_eq!(s4c31::attack(&victim, "foo", mac_len).unwrap(), None);
+    }
+
+    // Wall-clock timing over loopback, see s4c31::tests::challenge()
+    #[test]
+    #[ignore]
+    fn challenge() {
+        let key: [u8; 16] = rand::random();
+        let mac_len = 3;
+        let server = Server::start(&key, Duration::from_micros(500), mac_len).unwrap();
+
+        let found = attack(&server, "foo", mac_len, 5, 20).unwrap();
+        assert_eq!(found, Some(hmac_sha1(&key, b"foo")[..mac_len].to_vec()));
+    }
+}
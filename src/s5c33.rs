@@ -0,0 +1,89 @@
+use crate::bignum::{from_hex, modexp, to_bytes, BigUint, RandBigInt};
+use crate::s4c28::sha1;
+use rand::thread_rng;
+
+// The 1536-bit MODP group from RFC 3526
+const NIST_P: &str = "
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024
+    e088a67cc74020bbea63b139b22514a08798e3404ddef9519b3cd
+    3a431b302b0a6df25f14374fe1356d6d51c245e485b576625e7ec
+    6f44c42e9a637ed6b0bff5cb6f406b7edee386bfb5a899fa5ae9f
+    24117c4b1fe649286651ece45b3dc2007cb8a163bf0598da48361
+    c55d39a69163fa8fd24cf5f83655d23dca3ad961c62f356208552
+    bb9ed529077096966d670c354e4abc9804f1746c08ca237327fff
+    fffffffffffff";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Group {
+    pub p: BigUint,
+    pub g: BigUint,
+}
+
+impl Group {
+    pub fn nist() -> Self {
+        Self {
+            p: from_hex(NIST_P).unwrap(),
+            g: BigUint::from(2u32),
+        }
+    }
+}
+
+pub struct KeyPair {
+    private: BigUint,
+    pub public: BigUint,
+}
+
+impl KeyPair {
+    pub fn generate(group: &Group) -> Self {
+        let private = thread_rng().gen_biguint_below(&group.p);
+        let public = modexp(&group.g, &private, &group.p);
+        Self { private, public }
+    }
+
+    pub fn shared_secret(&self, group: &Group, other_public: &BigUint) -> BigUint {
+        modexp(other_public, &self.private, &group.p)
+    }
+}
+
+// Turn a shared secret into an AES-128 key: first 16 bytes of the SHA-1
+// of its big-endian representation
+pub fn derive_key(secret: &BigUint) -> [u8; 16] {
+    let digest = sha1(&to_bytes(secret, 0));
+    digest[..16].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_group() {
+        let group = Group {
+            p: BigUint::from(37u32),
+            g: BigUint::from(5u32),
+        };
+        let a = KeyPair::generate(&group);
+        let b = KeyPair::generate(&group);
+        let s = a.shared_secret(&group, &b.public);
+        assert_eq!(s, b.shared_secret(&group, &a.public));
+        assert!(s < group.p);
+    }
+
+    #[test]
+    fn challenge() {
+        let group = Group::nist();
+        assert_eq!(group.p.bits(), 1536);
+
+        let a = KeyPair::generate(&group);
+        let b = KeyPair::generate(&group);
+        let s = a.shared_secret(&group, &b.public);
+        assert_eq!(s, b.shared_secret(&group, &a.public));
+        assert_eq!(
+            derive_key(&s),
+            derive_key(&b.shared_secret(&group, &a.public))
+        );
+
+        let c = KeyPair::generate(&group);
+        assert_ne!(s, a.shared_secret(&group, &c.public));
+    }
+}
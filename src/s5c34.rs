@@ -0,0 +1,212 @@
+use crate::bignum::{BigUint, Zero};
+use crate::s2c09::{pkcs7_pad, pkcs7_unpad};
+use crate::s2c10::{aes_128_cbc_decrypt, aes_128_cbc_encrypt};
+use crate::s5c33::{derive_key, Group, KeyPair};
+
+// What A and B send each other
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    // A -> B: group and A's public key in one go
+    Params { group: Group, public: BigUint },
+    // A -> B: proposed group, B -> A: accepted group (challenge 35)
+    Group(Group),
+    // A public key on its own
+    Public(BigUint),
+    // AES-CBC(key, msg, iv) || iv
+    Data(Vec<u8>),
+}
+
+// Carries messages between A and B, and is free to read or replace them
+pub trait Channel {
+    fn a_to_b(&mut self, msg: Message) -> Message;
+    fn b_to_a(&mut self, msg: Message) -> Message;
+}
+
+// No one in the middle
+pub struct Direct;
+
+impl Channel for Direct {
+    fn a_to_b(&mut self, msg: Message) -> Message {
+        msg
+    }
+    fn b_to_a(&mut self, msg: Message) -> Message {
+        msg
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProtocolError {
+    // Got a different kind of message than the protocol calls for
+    Unexpected,
+    // Couldn't decrypt a Data message
+    Decrypt,
+    // A didn't get its own message back
+    Mismatch,
+}
+
+pub fn encrypt(key: &[u8; 16], msg: &[u8]) -> Vec<u8> {
+    let iv: [u8; 16] = rand::random();
+    let mut data = aes_128_cbc_encrypt(key, &iv, &pkcs7_pad(msg, 16)).unwrap();
+    data.extend_from_slice(&iv);
+    data
+}
+
+pub fn decrypt(key: &[u8; 16], data: &[u8]) -> Option<Vec<u8>> {
+    let ct_len = data.len().checked_sub(16)?;
+    let (ct, iv) = data.split_at(ct_len);
+    pkcs7_unpad(&aes_128_cbc_decrypt(key, iv, ct)?, 16)
+}
+
+// Once A and B have their keys: A sends msg, B decrypts it and sends it
+// back, encrypted again with its own IV, and A checks that it's the same.
+pub fn echo<C: Channel + ?Sized>(
+    channel: &mut C,
+    key_a: &[u8; 16],
+    key_b: &[u8; 16],
+    msg: &[u8],
+) -> Result<(), ProtocolError> {
+    let Message::Data(data) = channel.a_to_b(Message::Data(encrypt(key_a, msg))) else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let received = decrypt(key_b, &data).ok_or(ProtocolError::Decrypt)?;
+
+    let Message::Data(data) = channel.b_to_a(Message::Data(encrypt(key_b, &received))) else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let echoed = decrypt(key_a, &data).ok_or(ProtocolError::Decrypt)?;
+
+    match echoed == msg {
+        true => Ok(()),
+        false => Err(ProtocolError::Mismatch),
+    }
+}
+
+// A -> B: p, g, A
+// B -> A: B
+// then echo() msg
+pub fn run<C: Channel + ?Sized>(channel: &mut C, msg: &[u8]) -> Result<(), ProtocolError> {
+    let group = Group::nist();
+    let a = KeyPair::generate(&group);
+    let params = Message::Params {
+        group: group.clone(),
+        public: a.public.clone(),
+    };
+
+    let Message::Params {
+        group: group_b,
+        public: public_a,
+    } = channel.a_to_b(params)
+    else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let b = KeyPair::generate(&group_b);
+    let key_b = derive_key(&b.shared_secret(&group_b, &public_a));
+
+    let Message::Public(public_b) = channel.b_to_a(Message::Public(b.public.clone())) else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let key_a = derive_key(&a.shared_secret(&group, &public_b));
+
+    echo(channel, &key_a, &key_b, msg)
+}
+
+// Key-fixing attack: replace both public keys with p, so that both
+// sides end up with p^x mod p = 0 as their shared secret, and read
+// everything they say.
+#[derive(Default)]
+pub struct KeyFixing {
+    p: Option<BigUint>,
+    intercepted: Vec<Vec<u8>>,
+}
+
+impl KeyFixing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intercepted(&self) -> &[Vec<u8>] {
+        &self.intercepted
+    }
+
+    fn relay(&mut self, msg: Message) -> Message {
+        match msg {
+            Message::Params { group, .. } => {
+                let p = group.p.clone();
+                self.p = Some(p.clone());
+                Message::Params { group, public: p }
+            }
+            Message::Public(public) => match &self.p {
+                Some(p) => Message::Public(p.clone()),
+                None => Message::Public(public),
+            },
+            Message::Data(data) => {
+                let key = derive_key(&BigUint::zero());
+                if let Some(msg) = decrypt(&key, &data) {
+                    self.intercepted.push(msg);
+                }
+                Message::Data(data)
+            }
+            msg => msg,
+        }
+    }
+}
+
+impl Channel for KeyFixing {
+    fn a_to_b(&mut self, msg: Message) -> Message {
+        self.relay(msg)
+    }
+    fn b_to_a(&mut self, msg: Message) -> Message {
+        self.relay(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let key: [u8; 16] = rand::random();
+        for len in [0, 1, 15, 16, 17, 100] {
+            let msg = vec![b'x'; len];
+            assert_eq!(decrypt(&key, &encrypt(&key, &msg)), Some(msg));
+        }
+        assert_eq!(decrypt(&key, b"short"), None);
+    }
+
+    #[test]
+    fn honest() {
+        assert_eq!(run(&mut Direct, b"Hello, Bob"), Ok(()));
+    }
+
+    #[test]
+    fn challenge() {
+        let msg = b"Attack at dawn";
+        let mut mitm = KeyFixing::new();
+        assert_eq!(run(&mut mitm, msg), Ok(()));
+        assert_eq!(mitm.intercepted(), [msg.to_vec(), msg.to_vec()]);
+    }
+
+    // Tampering with the public keys without knowing what you're doing
+    // breaks the protocol
+    #[test]
+    fn clumsy_mitm() {
+        struct Clumsy;
+        impl Channel for Clumsy {
+            fn a_to_b(&mut self, msg: Message) -> Message {
+                match msg {
+                    Message::Params { group, .. } => Message::Params {
+                        public: group.g.clone(),
+                        group,
+                    },
+                    msg => msg,
+                }
+            }
+            fn b_to_a(&mut self, msg: Message) -> Message {
+                msg
+            }
+        }
+
+        assert!(run(&mut Clumsy, b"Hello, Bob").is_err());
+    }
+}
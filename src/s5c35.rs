@@ -0,0 +1,149 @@
+use crate::bignum::{BigUint, One, Zero};
+use crate::s5c33::{derive_key, Group, KeyPair};
+use crate::s5c34::{decrypt, echo, Channel, Message, ProtocolError};
+
+// A -> B: p, g
+// B -> A: the group it accepts
+// A -> B: A
+// B -> A: B
+// then echo() msg, like s5c34::run()
+pub fn run<C: Channel + ?Sized>(channel: &mut C, msg: &[u8]) -> Result<(), ProtocolError> {
+    let Message::Group(proposed) = channel.a_to_b(Message::Group(Group::nist())) else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let group_b = proposed.clone();
+    let Message::Group(group_a) = channel.b_to_a(Message::Group(proposed)) else {
+        return Err(ProtocolError::Unexpected);
+    };
+
+    let a = KeyPair::generate(&group_a);
+    let Message::Public(public_a) = channel.a_to_b(Message::Public(a.public.clone())) else {
+        return Err(ProtocolError::Unexpected);
+    };
+    let b = KeyPair::generate(&group_b);
+    let Message::Public(public_b) = channel.b_to_a(Message::Public(b.public.clone())) else {
+        return Err(ProtocolError::Unexpected);
+    };
+
+    let key_a = derive_key(&a.shared_secret(&group_a, &public_b));
+    let key_b = derive_key(&b.shared_secret(&group_b, &public_a));
+    echo(channel, &key_a, &key_b, msg)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EvilG {
+    One,
+    P,
+    PMinusOne,
+}
+
+impl EvilG {
+    fn g(self, p: &BigUint) -> BigUint {
+        match self {
+            EvilG::One => BigUint::one(),
+            EvilG::P => p.clone(),
+            EvilG::PMinusOne => p - 1u32,
+        }
+    }
+}
+
+// Replace g with a value that makes the shared secret predictable
+// during the negotiation, in both directions so that A and B agree:
+// - g = 1: all public keys are 1, and so is the secret
+// - g = p: all public keys are 0, and so is the secret
+// - g = p - 1: public keys are 1 or p - 1 depending on the parity of the
+//   private key, and the secret is p - 1 only if both are odd, which we
+//   can tell from the public keys
+pub struct MaliciousG {
+    evil: EvilG,
+    p: Option<BigUint>,
+    publics: Vec<BigUint>,
+    intercepted: Vec<Vec<u8>>,
+}
+
+impl MaliciousG {
+    pub fn new(evil: EvilG) -> Self {
+        Self {
+            evil,
+            p: None,
+            publics: Vec::new(),
+            intercepted: Vec::new(),
+        }
+    }
+
+    pub fn intercepted(&self) -> &[Vec<u8>] {
+        &self.intercepted
+    }
+
+    fn secret(&self) -> Option<BigUint> {
+        let p = self.p.as_ref()?;
+        let secret = match self.evil {
+            EvilG::One => BigUint::one(),
+            EvilG::P => BigUint::zero(),
+            EvilG::PMinusOne => {
+                let p_minus_1 = p - 1u32;
+                match self.publics.iter().all(|public| *public == p_minus_1) {
+                    true => p_minus_1,
+                    false => BigUint::one(),
+                }
+            }
+        };
+        Some(secret)
+    }
+
+    fn relay(&mut self, msg: Message) -> Message {
+        match msg {
+            Message::Group(group) => {
+                let g = self.evil.g(&group.p);
+                self.p = Some(group.p.clone());
+                Message::Group(Group { p: group.p, g })
+            }
+            Message::Public(public) => {
+                self.publics.push(public.clone());
+                Message::Public(public)
+            }
+            Message::Data(data) => {
+                if let Some(secret) = self.secret() {
+                    if let Some(msg) = decrypt(&derive_key(&secret), &data) {
+                        self.intercepted.push(msg);
+                    }
+                }
+                Message::Data(data)
+            }
+            msg => msg,
+        }
+    }
+}
+
+impl Channel for MaliciousG {
+    fn a_to_b(&mut self, msg: Message) -> Message {
+        self.relay(msg)
+    }
+    fn b_to_a(&mut self, msg: Message) -> Message {
+        self.relay(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s5c34::Direct;
+
+    #[test]
+    fn honest() {
+        assert_eq!(run(&mut Direct, b"Hello, Bob"), Ok(()));
+    }
+
+    #[test]
+    fn challenge() {
+        let msg = b"Attack at dawn";
+        for evil in [EvilG::One, EvilG::P, EvilG::PMinusOne] {
+            // g = p - 1 has 4 cases depending on the parity of a and b
+            for _ in 0..8 {
+                let mut mitm = MaliciousG::new(evil);
+                assert_eq!(run(&mut mitm, msg), Ok(()), "{:?}", evil);
+                assert_eq!(mitm.intercepted(), [msg.to_vec(), msg.to_vec()]);
+            }
+        }
+    }
+}
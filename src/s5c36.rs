@@ -0,0 +1,162 @@
+use crate::bignum::{from_bytes, modexp, to_bytes, BigUint, RandBigInt};
+use crate::consttime::ct_eq;
+use crate::s4c28::sha1;
+use crate::s4c31::hmac_sha1;
+use crate::s5c33::Group;
+use rand::thread_rng;
+
+// Secure Remote Password, SRP-6a style, with SHA-1 as the hash
+//
+// C -> S: I, A = g^a
+// S -> C: salt, B = kv + g^b
+// both: u = H(A || B)
+// C: x = H(salt || password), S = (B - k * g^x)^(a + u * x)
+// S: S = (A * v^u)^b
+// both: K = H(S)
+// C -> S: HMAC(K, salt)
+// S -> C: OK or not
+//
+// with k = H(N || PAD(g)), g padded to the length of N
+
+pub struct Params {
+    pub n: BigUint,
+    pub g: BigUint,
+    pub k: BigUint,
+}
+
+impl Params {
+    pub fn nist() -> Self {
+        let Group { p: n, g } = Group::nist();
+        let k = hash_int(&[&n.to_bytes_be(), &to_bytes(&g, n.to_bytes_be().len())]);
+        Self { n, g, k }
+    }
+}
+
+// Hash the concatenation of parts into an integer
+pub fn hash_int(parts: &[&[u8]]) -> BigUint {
+    from_bytes(&sha1(&parts.concat()))
+}
+
+pub fn session_key(s: &BigUint) -> [u8; 20] {
+    sha1(&to_bytes(s, 0))
+}
+
+// What a client can ask of a server
+pub trait SrpServer {
+    // Send I and A, get the salt and B back if the user exists
+    fn hello(&mut self, email: &str, public: &BigUint) -> Option<(Vec<u8>, BigUint)>;
+    // Send HMAC(K, salt), see if it's accepted
+    fn verify(&mut self, mac: &[u8]) -> bool;
+}
+
+// Knows one user, and keeps track of one login attempt at a time.
+// The fields are shared with the simplified server of challenge 38.
+pub struct Server {
+    pub(crate) params: Params,
+    pub(crate) email: String,
+    pub(crate) salt: Vec<u8>,
+    pub(crate) v: BigUint,
+    // HMAC expected for the ongoing attempt
+    pub(crate) expected: Option<Vec<u8>>,
+}
+
+impl Server {
+    pub fn new(email: &str, password: &str) -> Self {
+        let params = Params::nist();
+        let salt = rand::random::<[u8; 16]>().to_vec();
+        let x = hash_int(&[&salt, password.as_bytes()]);
+        let v = modexp(&params.g, &x, &params.n);
+
+        Self {
+            params,
+            email: email.to_string(),
+            salt,
+            v,
+            expected: None,
+        }
+    }
+}
+
+impl SrpServer for Server {
+    fn hello(&mut self, email: &str, public: &BigUint) -> Option<(Vec<u8>, BigUint)> {
+        self.expected = None;
+        if email != self.email {
+            return None;
+        }
+
+        let Params { n, g, k } = &self.params;
+        let b = thread_rng().gen_biguint_below(n);
+        let public_b = (k * &self.v + modexp(g, &b, n)) % n;
+
+        let u = hash_int(&[&public.to_bytes_be(), &public_b.to_bytes_be()]);
+        let s = modexp(&(public * modexp(&self.v, &u, n)), &b, n);
+        self.expected = Some(hmac_sha1(&session_key(&s), &self.salt));
+
+        Some((self.salt.clone(), public_b))
+    }
+
+    fn verify(&mut self, mac: &[u8]) -> bool {
+        match self.expected.take() {
+            Some(expected) => ct_eq(&expected, mac),
+            None => false,
+        }
+    }
+}
+
+pub fn login<S: SrpServer + ?Sized>(server: &mut S, email: &str, password: &str) -> bool {
+    let Params { n, g, k } = Params::nist();
+    let a = thread_rng().gen_biguint_below(&n);
+    let public_a = modexp(&g, &a, &n);
+
+    let Some((salt, public_b)) = server.hello(email, &public_a) else {
+        return false;
+    };
+
+    let u = hash_int(&[&public_a.to_bytes_be(), &public_b.to_bytes_be()]);
+    let x = hash_int(&[&salt, password.as_bytes()]);
+    // B - k * g^x, kept positive
+    let base = (&n + public_b % &n - (k * modexp(&g, &x, &n)) % &n) % &n;
+    let s = modexp(&base, &(a + u * x), &n);
+
+    server.verify(&hmac_sha1(&session_key(&s), &salt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiplier() {
+        // SRP-6a, not the k = 3 of SRP-6
+        let Params { n, g, k } = Params::nist();
+        assert_ne!(k, BigUint::from(3u32));
+        let mut padded_g = vec![0u8; n.to_bytes_be().len() - 1];
+        padded_g.extend_from_slice(&g.to_bytes_be());
+        assert_eq!(k, from_bytes(&sha1(&[n.to_bytes_be(), padded_g].concat())));
+    }
+
+    #[test]
+    fn challenge() {
+        let mut server = Server::new("alice@example.com", "correct horse battery staple");
+        assert!(login(
+            &mut server,
+            "alice@example.com",
+            "correct horse battery staple"
+        ));
+        assert!(!login(&mut server, "alice@example.com", "hunter2"));
+        assert!(!login(
+            &mut server,
+            "bob@example.com",
+            "correct horse battery staple"
+        ));
+    }
+
+    #[test]
+    fn no_replay() {
+        let mut server = Server::new("alice@example.com", "hunter2");
+        assert!(!server.verify(b"anything"));
+        assert!(login(&mut server, "alice@example.com", "hunter2"));
+        // The expected HMAC is gone after one try
+        assert!(!server.verify(b"anything"));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::bignum::{BigUint, Zero};
+use crate::s4c31::hmac_sha1;
+use crate::s5c36::{session_key, Params, SrpServer};
+
+// Log in without the password by sending A = multiple * N: the server
+// computes S = (A * v^u)^b = 0 mod N, so K = H(0), which we know.
+pub fn login_without_password<S: SrpServer + ?Sized>(
+    server: &mut S,
+    email: &str,
+    multiple: u32,
+) -> bool {
+    let public_a = Params::nist().n * multiple;
+    let Some((salt, _)) = server.hello(email, &public_a) else {
+        return false;
+    };
+
+    let key = session_key(&BigUint::zero());
+    server.verify(&hmac_sha1(&key, &salt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s5c36::Server;
+
+    #[test]
+    fn challenge() {
+        let mut server = Server::new("alice@example.com", "correct horse battery staple");
+        for multiple in 0..=3 {
+            assert!(login_without_password(
+                &mut server,
+                "alice@example.com",
+                multiple
+            ));
+        }
+        assert!(!login_without_password(&mut server, "bob@example.com", 0));
+    }
+}
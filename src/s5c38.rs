@@ -0,0 +1,151 @@
+use crate::bignum::{modexp, BigUint, One, RandBigInt};
+use crate::s4c31::hmac_sha1;
+use crate::s5c36::{self, hash_int, session_key, Params, SrpServer};
+use rand::thread_rng;
+
+// Simplified SRP: B and u don't depend on the password any more
+//
+// C -> S: I, A = g^a
+// S -> C: salt, B = g^b, u = random 128-bit number
+// C: x = H(salt || password), S = B^(a + u * x)
+// S: S = (A * v^u)^b
+// C -> S: HMAC(K, salt) with K = H(S)
+
+pub trait SimpleServer {
+    // Send I and A, get the salt, B and u back if the user exists
+    fn hello(&mut self, email: &str, public: &BigUint) -> Option<(Vec<u8>, BigUint, BigUint)>;
+    fn verify(&mut self, mac: &[u8]) -> bool;
+}
+
+// Stores the same things as the challenge 36 server, it just talks
+// differently
+pub struct Server(s5c36::Server);
+
+impl Server {
+    pub fn new(email: &str, password: &str) -> Self {
+        Self(s5c36::Server::new(email, password))
+    }
+}
+
+impl SimpleServer for Server {
+    fn hello(&mut self, email: &str, public: &BigUint) -> Option<(Vec<u8>, BigUint, BigUint)> {
+        let server = &mut self.0;
+        server.expected = None;
+        if email != server.email {
+            return None;
+        }
+
+        let Params { n, g, .. } = &server.params;
+        let mut rng = thread_rng();
+        let b = rng.gen_biguint_below(n);
+        let public_b = modexp(g, &b, n);
+        let u = rng.gen_biguint(128);
+
+        let s = modexp(&(public * modexp(&server.v, &u, n)), &b, n);
+        server.expected = Some(hmac_sha1(&session_key(&s), &server.salt));
+
+        Some((server.salt.clone(), public_b, u))
+    }
+
+    fn verify(&mut self, mac: &[u8]) -> bool {
+        SrpServer::verify(&mut self.0, mac)
+    }
+}
+
+pub fn login<S: SimpleServer + ?Sized>(server: &mut S, email: &str, password: &str) -> bool {
+    let Params { n, g, .. } = Params::nist();
+    let a = thread_rng().gen_biguint_below(&n);
+    let public_a = modexp(&g, &a, &n);
+
+    let Some((salt, public_b, u)) = server.hello(email, &public_a) else {
+        return false;
+    };
+
+    let x = hash_int(&[&salt, password.as_bytes()]);
+    let s = modexp(&public_b, &(a + u * x), &n);
+    server.verify(&hmac_sha1(&session_key(&s), &salt))
+}
+
+// Pose as the server with b = 1, B = g, u = 1 and an empty salt, and
+// record what the client sends. Then
+// S = B^(a + x) = A * g^x = A * v
+// which we can compute for any candidate password.
+pub struct EvilServer {
+    params: Params,
+    public_a: Option<BigUint>,
+    mac: Option<Vec<u8>>,
+}
+
+impl Default for EvilServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvilServer {
+    pub fn new() -> Self {
+        Self {
+            params: Params::nist(),
+            public_a: None,
+            mac: None,
+        }
+    }
+
+    // Offline dictionary attack on what the client sent
+    pub fn crack<'a>(&self, dictionary: &[&'a str]) -> Option<&'a str> {
+        let Params { n, g, .. } = &self.params;
+        let public_a = self.public_a.as_ref()?;
+        let mac = self.mac.as_ref()?;
+
+        dictionary.iter().copied().find(|password| {
+            let x = hash_int(&[password.as_bytes()]);
+            let s = public_a * modexp(g, &x, n) % n;
+            hmac_sha1(&session_key(&s), b"") == *mac
+        })
+    }
+}
+
+impl SimpleServer for EvilServer {
+    fn hello(&mut self, _email: &str, public: &BigUint) -> Option<(Vec<u8>, BigUint, BigUint)> {
+        self.public_a = Some(public.clone());
+        Some((Vec::new(), self.params.g.clone(), BigUint::one()))
+    }
+
+    // Say no, the user will think they mistyped their password
+    fn verify(&mut self, mac: &[u8]) -> bool {
+        self.mac = Some(mac.to_vec());
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    const DICTIONARY: [&str; 16] = [
+        "123456", "password", "12345678", "qwerty", "abc123", "monkey", "letmein", "dragon",
+        "111111", "baseball", "iloveyou", "trustno1", "sunshine", "master", "welcome", "shadow",
+    ];
+
+    #[test]
+    fn simplified_srp() {
+        let mut server = Server::new("alice@example.com", "hunter2");
+        assert!(login(&mut server, "alice@example.com", "hunter2"));
+        assert!(!login(&mut server, "alice@example.com", "hunter3"));
+        assert!(!login(&mut server, "bob@example.com", "hunter2"));
+    }
+
+    #[test]
+    fn challenge() {
+        let password = DICTIONARY.choose(&mut thread_rng()).unwrap();
+
+        let mut mitm = EvilServer::new();
+        assert!(!login(&mut mitm, "alice@example.com", password));
+        assert_eq!(mitm.crack(&DICTIONARY), Some(*password));
+
+        let mut mitm = EvilServer::new();
+        login(&mut mitm, "alice@example.com", "not in the dictionary");
+        assert_eq!(mitm.crack(&DICTIONARY), None);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::bignum::{gen_prime, invmod, modexp, BigUint, One};
+
+// Textbook RSA: no padding, just m^e mod n
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RsaError {
+    // e has no inverse modulo (p - 1)(q - 1)
+    NotInvertible,
+    // The message, as an integer, is not smaller than n
+    MessageTooLarge,
+    // Fewer than MIN_BITS bits asked for: too few primes to pick from
+    KeyTooSmall,
+}
+
+pub const MIN_BITS: u64 = 16;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey {
+    pub e: BigUint,
+    pub n: BigUint,
+}
+
+#[derive(Clone)]
+pub struct PrivateKey {
+    d: BigUint,
+    pub n: BigUint,
+}
+
+pub struct KeyPair {
+    pub public: PublicKey,
+    pub private: PrivateKey,
+}
+
+impl PublicKey {
+    // Size of n in bytes
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8) as usize
+    }
+
+    pub fn encrypt(&self, m: &BigUint) -> Result<BigUint, RsaError> {
+        if *m >= self.n {
+            return Err(RsaError::MessageTooLarge);
+        }
+        Ok(modexp(m, &self.e, &self.n))
+    }
+}
+
+impl PrivateKey {
+    pub fn decrypt(&self, c: &BigUint) -> Result<BigUint, RsaError> {
+        if *c >= self.n {
+            return Err(RsaError::MessageTooLarge);
+        }
+        Ok(modexp(c, &self.d, &self.n))
+    }
+}
+
+impl KeyPair {
+    pub fn from_primes(p: &BigUint, q: &BigUint, e: u32) -> Result<Self, RsaError> {
+        let one = BigUint::one();
+        let n = p * q;
+        let et = (p - &one) * (q - &one);
+        let e = BigUint::from(e);
+        let d = invmod(&e, &et).ok_or(RsaError::NotInvertible)?;
+
+        Ok(Self {
+            public: PublicKey { e, n: n.clone() },
+            private: PrivateKey { d, n },
+        })
+    }
+
+    // Key with an n of exactly bits bits, at least MIN_BITS.
+    // Pick new primes until e is invertible, unless it never can be.
+    pub fn generate(bits: u64, e: u32) -> Result<Self, RsaError> {
+        if bits < MIN_BITS {
+            return Err(RsaError::KeyTooSmall);
+        }
+        if e < 3 || e.is_multiple_of(2) {
+            return Err(RsaError::NotInvertible);
+        }
+
+        loop {
+            let p = gen_prime(bits / 2);
+            let q = gen_prime(bits - bits / 2);
+            if p == q || (&p * &q).bits() != bits {
+                continue;
+            }
+            match Self::from_primes(&p, &q, e) {
+                Ok(pair) => return Ok(pair),
+                Err(RsaError::NotInvertible) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::from_bytes;
+
+    fn n(x: u32) -> BigUint {
+        BigUint::from(x)
+    }
+
+    #[test]
+    fn small() {
+        // The textbook example
+        let pair = KeyPair::from_primes(&n(61), &n(53), 17).unwrap();
+        assert_eq!(pair.public.n, n(3233));
+        assert_eq!(pair.public.encrypt(&n(65)), Ok(n(2790)));
+        assert_eq!(pair.private.decrypt(&n(2790)), Ok(n(65)));
+
+        assert_eq!(
+            pair.public.encrypt(&n(3233)),
+            Err(RsaError::MessageTooLarge)
+        );
+        assert_eq!(
+            pair.private.decrypt(&n(4000)),
+            Err(RsaError::MessageTooLarge)
+        );
+
+        // (11 - 1)(17 - 1) = 160 is a multiple of 5
+        assert!(matches!(
+            KeyPair::from_primes(&n(11), &n(17), 5),
+            Err(RsaError::NotInvertible)
+        ));
+        assert!(matches!(
+            KeyPair::generate(512, 4),
+            Err(RsaError::NotInvertible)
+        ));
+
+        for bits in [0, 1, 3, MIN_BITS - 1] {
+            assert!(matches!(
+                KeyPair::generate(bits, 3),
+                Err(RsaError::KeyTooSmall)
+            ));
+        }
+        let pair = KeyPair::generate(MIN_BITS, 3).unwrap();
+        assert_eq!(pair.public.n.bits(), MIN_BITS);
+    }
+
+    #[test]
+    fn challenge() {
+        for e in [3, 65537] {
+            let pair = KeyPair::generate(1024, e).unwrap();
+            assert_eq!(pair.public.n.bits(), 1024);
+            assert_eq!(pair.public.size(), 128);
+
+            let m = from_bytes(b"Attack at dawn");
+            let c = pair.public.encrypt(&m).unwrap();
+            assert_ne!(c, m);
+            assert_eq!(pair.private.decrypt(&c), Ok(m));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::bignum::{crt, exact_nth_root, BigUint, ToPrimitive};
+use crate::s5c39::PublicKey;
+
+// Håstad's broadcast attack: the same message encrypted with e different
+// public keys that all use exponent e, and no padding.
+// By CRT, get m^e mod n1 * n2 * ... * ne; since m is smaller than each
+// n, m^e is smaller than the product, so that's m^e itself, and we just
+// need to take the e-th root.
+//
+// Returns None if the keys don't all use e = the number of ciphertexts,
+// their moduli aren't coprime, or the result isn't an exact e-th power.
+pub fn attack(cts: &[(BigUint, PublicKey)]) -> Option<BigUint> {
+    let e = cts.len();
+    if e == 0 || cts.iter().any(|(_, key)| key.e.to_usize() != Some(e)) {
+        return None;
+    }
+
+    let pairs: Vec<_> = cts
+        .iter()
+        .map(|(c, key)| (c.clone(), key.n.clone()))
+        .collect();
+    let m_e = crt(&pairs)?;
+    exact_nth_root(&m_e, e as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::from_bytes;
+    use crate::s5c39::KeyPair;
+
+    #[test]
+    fn challenge() {
+        let m = from_bytes(b"Attack at dawn, or maybe a bit later");
+        let cts: Vec<_> = (0..3)
+            .map(|_| {
+                let pair = KeyPair::generate(512, 3).unwrap();
+                (pair.public.encrypt(&m).unwrap(), pair.public)
+            })
+            .collect();
+
+        assert_eq!(attack(&cts), Some(m));
+        // Not enough ciphertexts
+        assert_eq!(attack(&cts[..2]), None);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::bignum::{from_bytes, invmod, modexp, to_bytes, BigUint, Integer, One, RandBigInt};
+use crate::oracle::DecryptionOracle;
+use crate::s5c39::PublicKey;
+use rand::thread_rng;
+
+// use a separate module for privacy
+mod oracle {
+    use crate::bignum::{from_bytes, to_bytes};
+    use crate::oracle::DecryptionOracle;
+    use crate::s4c28::{sha1, DIGEST_SIZE};
+    use crate::s5c39::{KeyPair, PublicKey};
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+
+    // Decrypts anything, but only once: a server that accepts encrypted
+    // blobs and won't decrypt the same one twice.
+    pub struct Oracle {
+        pair: KeyPair,
+        seen: RefCell<HashSet<[u8; DIGEST_SIZE]>>,
+    }
+
+    impl Oracle {
+        pub fn new(bits: u64) -> Self {
+            Self {
+                pair: KeyPair::generate(bits, 65537).unwrap(),
+                seen: RefCell::new(HashSet::new()),
+            }
+        }
+
+        pub fn public(&self) -> &PublicKey {
+            &self.pair.public
+        }
+    }
+
+    impl DecryptionOracle for Oracle {
+        fn decrypt(&self, ct: &[u8]) -> Option<Vec<u8>> {
+            let c = from_bytes(ct);
+            let pt = self.pair.private.decrypt(&c).ok()?;
+
+            // Hash the integer rather than the bytes we were given,
+            // otherwise leading 0s would get around the check
+            let hash = sha1(&c.to_bytes_be());
+            if !self.seen.borrow_mut().insert(hash) {
+                return None;
+            }
+
+            Some(to_bytes(&pt, 0))
+        }
+    }
+}
+
+pub use oracle::Oracle;
+
+// Get the victim to decrypt a ciphertext it has already seen: multiply
+// it by S^e for some random S, so that it decrypts to S * m, and divide
+// by S.
+pub fn attack<T: DecryptionOracle + ?Sized>(
+    victim: &T,
+    public: &PublicKey,
+    ct: &[u8],
+) -> Option<Vec<u8>> {
+    let PublicKey { e, n } = public;
+    let c = from_bytes(ct);
+
+    let mut rng = thread_rng();
+    let s = loop {
+        let s = rng.gen_biguint_range(&BigUint::from(2u32), n);
+        if s.gcd(n).is_one() {
+            break s;
+        }
+    };
+
+    let blinded = modexp(&s, e, n) * c % n;
+    let pt = from_bytes(&victim.decrypt(&to_bytes(&blinded, public.size()))?);
+    let m = pt * invmod(&s, n)? % n;
+
+    Some(to_bytes(&m, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oracle::QueryCounter;
+
+    #[test]
+    fn once() {
+        let victim = Oracle::new(512);
+        let m = from_bytes(b"secret");
+        let c = victim.public().encrypt(&m).unwrap();
+        let ct = to_bytes(&c, victim.public().size());
+
+        assert_eq!(victim.decrypt(&ct), Some(b"secret".to_vec()));
+        assert_eq!(victim.decrypt(&ct), None);
+        // Same integer, different bytes
+        assert_eq!(victim.decrypt(&[&[0u8][..], &ct].concat()), None);
+    }
+
+    #[test]
+    fn challenge() {
+        let victim = Oracle::new(1024);
+        let msg = br#"{time: 1356304276, social: "555-55-5555"}"#;
+        let c = victim.public().encrypt(&from_bytes(msg)).unwrap();
+        let ct = to_bytes(&c, victim.public().size());
+
+        // The legitimate user had it decrypted already
+        assert!(victim.decrypt(&ct).is_some());
+        assert_eq!(victim.decrypt(&ct), None);
+
+        let counter = QueryCounter::new(&victim);
+        assert_eq!(attack(&counter, victim.public(), &ct), Some(msg.to_vec()));
+        assert_eq!(counter.count(), 1);
+    }
+}
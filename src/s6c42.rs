@@ -0,0 +1,156 @@
+use crate::bignum::{cube_root, from_bytes, to_bytes, ToPrimitive};
+use crate::consttime::ct_eq;
+use crate::s4c28::{sha1, DIGEST_SIZE};
+use crate::s5c39::{PrivateKey, PublicKey, RsaError};
+
+// DER encoding of the DigestInfo for SHA-1, up to the hash itself
+pub const SHA1_DIGEST_INFO: [u8; 15] = [
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+];
+
+// PKCS#1 v1.5 signature padding for a SHA-1 hash, k bytes long:
+// 00 01 FF .. FF 00 DigestInfo hash
+// with at least 8 bytes of FF
+pub fn encode(msg: &[u8], k: usize) -> Result<Vec<u8>, RsaError> {
+    let t_len = SHA1_DIGEST_INFO.len() + DIGEST_SIZE;
+    let ff_len = k
+        .checked_sub(t_len + 3)
+        .filter(|&len| len >= 8)
+        .ok_or(RsaError::MessageTooLarge)?;
+
+    let mut em = vec![0x00, 0x01];
+    em.resize(2 + ff_len, 0xff);
+    em.push(0x00);
+    em.extend_from_slice(&SHA1_DIGEST_INFO);
+    em.extend_from_slice(&sha1(msg));
+    Ok(em)
+}
+
+pub fn sign(private: &PrivateKey, public: &PublicKey, msg: &[u8]) -> Result<Vec<u8>, RsaError> {
+    let k = public.size();
+    let em = from_bytes(&encode(msg, k)?);
+    // Raw RSA with d, which is what decryption does
+    let s = private.decrypt(&em)?;
+    Ok(to_bytes(&s, k))
+}
+
+// s^e mod n as k bytes
+fn open(public: &PublicKey, sig: &[u8]) -> Option<Vec<u8>> {
+    let k = public.size();
+    if sig.len() != k {
+        return None;
+    }
+    let m = public.encrypt(&from_bytes(sig)).ok()?;
+    Some(to_bytes(&m, k))
+}
+
+// Rebuild the expected padding and compare all of it
+pub fn verify_strict(public: &PublicKey, msg: &[u8], sig: &[u8]) -> bool {
+    let Some(em) = open(public, sig) else {
+        return false;
+    };
+    match encode(msg, public.size()) {
+        Ok(expected) => ct_eq(&em, &expected),
+        Err(_) => false,
+    }
+}
+
+// Parse the padding from the left, and stop once we have the hash,
+// never checking that it's right-justified
+pub fn verify_sloppy(public: &PublicKey, msg: &[u8], sig: &[u8]) -> bool {
+    let Some(em) = open(public, sig) else {
+        return false;
+    };
+    let Some(rest) = em.strip_prefix(&[0x00, 0x01]) else {
+        return false;
+    };
+    let ff_len = rest.iter().take_while(|&&b| b == 0xff).count();
+    if ff_len == 0 {
+        return false;
+    }
+    let Some(rest) = rest[ff_len..].strip_prefix(&[0x00]) else {
+        return false;
+    };
+    let Some(rest) = rest.strip_prefix(&SHA1_DIGEST_INFO) else {
+        return false;
+    };
+
+    rest.len() >= DIGEST_SIZE && rest[..DIGEST_SIZE] == sha1(msg)
+}
+
+// Bleichenbacher's e = 3 forgery: build
+// 00 01 FF 00 DigestInfo hash 00 .. 00
+// and take the cube root, rounded up. Its cube is a bit larger, but the
+// difference only affects the garbage at the end if there's enough of
+// it, which is the case with 1024-bit keys.
+pub fn forge(public: &PublicKey, msg: &[u8]) -> Option<Vec<u8>> {
+    if public.e.to_u32() != Some(3) {
+        return None;
+    }
+    let k = public.size();
+
+    let mut prefix = vec![0x00, 0x01, 0xff, 0x00];
+    prefix.extend_from_slice(&SHA1_DIGEST_INFO);
+    prefix.extend_from_slice(&sha1(msg));
+    let garbage_len = k.checked_sub(prefix.len())?;
+
+    let mut low = prefix.clone();
+    low.resize(k, 0x00);
+    let low = from_bytes(&low);
+
+    let mut s = cube_root(&low);
+    if s.pow(3) < low {
+        s += 1u32;
+    }
+
+    // Check that the prefix survived
+    let cube = to_bytes(&s.pow(3), k);
+    match cube.len() == k && cube[..k - garbage_len] == prefix[..] {
+        true => Some(to_bytes(&s, k)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s5c39::KeyPair;
+
+    #[test]
+    fn padding() {
+        let em = encode(b"hi mom", 128).unwrap();
+        assert_eq!(em.len(), 128);
+        assert_eq!(&em[..2], [0x00, 0x01]);
+        assert_eq!(em[128 - 36], 0x00);
+        assert!(em[2..128 - 36].iter().all(|&b| b == 0xff));
+        assert!(em.ends_with(&sha1(b"hi mom")));
+
+        assert_eq!(encode(b"hi mom", 45), Err(RsaError::MessageTooLarge));
+        assert!(encode(b"hi mom", 46).is_ok());
+    }
+
+    #[test]
+    fn legit() {
+        let pair = KeyPair::generate(1024, 3).unwrap();
+        let sig = sign(&pair.private, &pair.public, b"hi mom").unwrap();
+
+        assert!(verify_strict(&pair.public, b"hi mom", &sig));
+        assert!(verify_sloppy(&pair.public, b"hi mom", &sig));
+        assert!(!verify_strict(&pair.public, b"hi dad", &sig));
+        assert!(!verify_sloppy(&pair.public, b"hi dad", &sig));
+    }
+
+    #[test]
+    fn challenge() {
+        let pair = KeyPair::generate(1024, 3).unwrap();
+        let sig = forge(&pair.public, b"hi mom").unwrap();
+
+        assert!(verify_sloppy(&pair.public, b"hi mom", &sig));
+        assert!(!verify_strict(&pair.public, b"hi mom", &sig));
+        assert!(!verify_sloppy(&pair.public, b"hi dad", &sig));
+
+        // e = 65537 is out of reach
+        let pair = KeyPair::generate(1024, 65537).unwrap();
+        assert_eq!(forge(&pair.public, b"hi mom"), None);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::bignum::{from_bytes, from_hex, invmod, modexp, BigUint, RandBigInt, Zero};
+use crate::s4c28::sha1;
+use rand::thread_rng;
+use std::ops::Range;
+
+// DSA with SHA-1, over any group
+#[derive(Debug, PartialEq, Clone)]
+pub struct Params {
+    pub p: BigUint,
+    pub q: BigUint,
+    pub g: BigUint,
+}
+
+impl Params {
+    // The parameters from the challenge
+    pub fn challenge() -> Self {
+        Self {
+            p: from_hex(
+                "800000000000000089e1855218a0e7dac38136ffafa72eda7
+                 859f2171e25e65eac698c1702578b07dc2a1076da241c76c6
+                 2d374d8389ea5aeffd3226a0530cc565f3bf6b50929139ebe
+                 ac04f48c3c84afb796d61e5a4f9a8fda812ab59494232c7d2
+                 b4deb50aa18ee9e132bfa85ac4374d7f9091abc3d015efc87
+                 1a584471bb1",
+            )
+            .unwrap(),
+            q: from_hex("f4f47f05794b256174bba6e9b396a7707e563c5b").unwrap(),
+            g: from_hex(
+                "5958c9d3898b224b12672c0b98e06c60df923cb8bc999d119
+                 458fef538b8fa4046c8db53039db620c094c9fa077ef389b5
+                 322a559946a71903f990f1f7e0e025e2d7f7cf494aff1a047
+                 0f5b64c36b625a097f1651fe775323556fe00b3608c887892
+                 878480e99041be601a62166ca6894bdd41a7054ec89f756ba
+                 9fc95302291",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature {
+    pub r: BigUint,
+    pub s: BigUint,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey {
+    pub params: Params,
+    pub y: BigUint,
+}
+
+const SIGN_TRIES: usize = 16;
+
+pub struct PrivateKey {
+    params: Params,
+    x: BigUint,
+}
+
+// SHA-1 of the message as an integer
+pub fn hash(msg: &[u8]) -> BigUint {
+    from_bytes(&sha1(msg))
+}
+
+impl PrivateKey {
+    pub fn generate(params: &Params) -> Self {
+        let x = thread_rng().gen_biguint_range(&BigUint::from(1u32), &params.q);
+        Self {
+            params: params.clone(),
+            x,
+        }
+    }
+
+    pub fn public(&self) -> PublicKey {
+        let Params { p, g, .. } = &self.params;
+        PublicKey {
+            params: self.params.clone(),
+            y: modexp(g, &self.x, p),
+        }
+    }
+
+    // Sign with a given nonce, and no sanity checks on the result.
+    // None if k isn't invertible mod q.
+    pub fn sign_with_k(&self, msg: &[u8], k: &BigUint) -> Option<Signature> {
+        let Params { p, q, g } = &self.params;
+        let r = modexp(g, k, p) % q;
+        let s = invmod(k, q)? * (hash(msg) + &self.x * &r) % q;
+        Some(Signature { r, s })
+    }
+
+    // With sane parameters r or s is 0 with negligible probability, so
+    // retrying a few times is plenty. With bad ones (g = 0 for example)
+    // it may never work: None.
+    pub fn sign(&self, msg: &[u8]) -> Option<Signature> {
+        let mut rng = thread_rng();
+        (0..SIGN_TRIES).find_map(|_| {
+            let k = rng.gen_biguint_range(&BigUint::from(1u32), &self.params.q);
+            self.sign_with_k(msg, &k)
+                .filter(|sig| !sig.r.is_zero() && !sig.s.is_zero())
+        })
+    }
+}
+
+impl PublicKey {
+    pub fn verify(&self, msg: &[u8], sig: &Signature) -> bool {
+        let q = &self.params.q;
+        let in_range = |v: &BigUint| !v.is_zero() && v < q;
+        in_range(&sig.r) && in_range(&sig.s) && self.verify_unchecked(msg, sig)
+    }
+
+    // Same without checking that 0 < r < q and 0 < s < q
+    pub fn verify_unchecked(&self, msg: &[u8], sig: &Signature) -> bool {
+        let Params { p, q, g } = &self.params;
+        let Some(w) = invmod(&sig.s, q) else {
+            return false;
+        };
+        let u1 = hash(msg) * &w % q;
+        let u2 = &sig.r * &w % q;
+        let v = modexp(g, &u1, p) * modexp(&self.y, &u2, p) % p % q;
+        v == sig.r
+    }
+}
+
+// With the nonce, the private key is x = (s * k - H(m)) / r mod q
+pub fn x_from_k(
+    params: &Params,
+    msg_hash: &BigUint,
+    sig: &Signature,
+    k: &BigUint,
+) -> Option<BigUint> {
+    let q = &params.q;
+    let sk = &sig.s * k % q;
+    let h = msg_hash % q;
+    Some((sk + q - h) * invmod(&sig.r, q)? % q)
+}
+
+// Find the nonce used for sig by trying all values in ks, checking each
+// against r = (g^k mod p) mod q, and get the private key from it.
+// Several nonces can give the same r, so check the key against y too.
+pub fn recover_key(
+    public: &PublicKey,
+    msg: &[u8],
+    sig: &Signature,
+    ks: Range<u64>,
+) -> Option<BigUint> {
+    let params = &public.params;
+    let Params { p, q, g } = params;
+    let h = hash(msg);
+
+    // g^k, one multiplication at a time
+    let mut gk = modexp(g, &BigUint::from(ks.start), p);
+    for k in ks {
+        if &gk % q == sig.r {
+            let x = x_from_k(params, &h, sig, &BigUint::from(k));
+            if let Some(x) = x.filter(|x| modexp(g, x, p) == public.y) {
+                return Some(x);
+            }
+        }
+        gk = gk * g % p;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_verify() {
+        let params = Params::challenge();
+        let private = PrivateKey::generate(&params);
+        let public = private.public();
+
+        let sig = private.sign(b"hi mom").unwrap();
+        assert!(public.verify(b"hi mom", &sig));
+        assert!(!public.verify(b"hi dad", &sig));
+
+        let other = PrivateKey::generate(&params).public();
+        assert!(!other.verify(b"hi mom", &sig));
+    }
+
+    #[test]
+    fn small_nonce() {
+        let params = Params::challenge();
+        let private = PrivateKey::generate(&params);
+        let public = private.public();
+        let sig = private
+            .sign_with_k(b"hi mom", &BigUint::from(1234u32))
+            .unwrap();
+
+        let x = recover_key(&public, b"hi mom", &sig, 0..1 << 12).unwrap();
+        assert_eq!(modexp(&params.g, &x, &params.p), public.y);
+        assert_eq!(recover_key(&public, b"hi mom", &sig, 0..1000), None);
+
+        // Right nonce, wrong public key
+        let other = PrivateKey::generate(&params).public();
+        assert_eq!(recover_key(&other, b"hi mom", &sig, 0..1 << 12), None);
+    }
+
+    #[test]
+    fn challenge() {
+        let msg = b"For those that envy a MC it can be hazardous to your health\n\
+                    So be friendly, a matter of life and death, just like a etch-a-sketch\n";
+        assert_eq!(
+            hash(msg),
+            from_hex("d2d0714f014a9784047eaeccf956520045c45265").unwrap()
+        );
+        let sig = Signature {
+            r: "548099063082341131477253921760299949438196259240"
+                .parse()
+                .unwrap(),
+            s: "857042759984254168557880549501802188789837994940"
+                .parse()
+                .unwrap(),
+        };
+
+        let public = PublicKey {
+            params: Params::challenge(),
+            y: from_hex(
+                "84ad4719d044495496a3201c8ff484feb45b962e7302e56a
+                 392aee4abab3e4bdebf2955b4736012f21a08084056b19bcd
+                 7fee56048e004e44984e2f411788efdc837a0d2e5abb7b555
+                 039fd243ac01f0fb2ed1dec568280ce678e931868d23eb095
+                 fde9d3779191b8c0299d6e07bbb283e6633451e535c45513b
+                 2d33c99ea17",
+            )
+            .unwrap(),
+        };
+
+        let x = recover_key(&public, msg, &sig, 0..1 << 16).unwrap();
+        let fingerprint = sha1(x.to_str_radix(16).as_bytes());
+        assert_eq!(
+            hex::encode(fingerprint),
+            "0954edd5e0afe5542a4adf012611a91912a3ec16"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::bignum::{invmod, modexp, BigUint};
+use crate::s6c43::{x_from_k, PublicKey, Signature};
+use std::collections::HashMap;
+
+// Given (message hash, signature) pairs made with the same key, find two
+// that used the same nonce (they have the same r), and get the private
+// key from them:
+// s1 - s2 = (H(m1) - H(m2)) / k mod q
+// so k = (H(m1) - H(m2)) / (s1 - s2) mod q
+pub fn recover_key(public: &PublicKey, sigs: &[(BigUint, Signature)]) -> Option<BigUint> {
+    let params = &public.params;
+    let q = &params.q;
+
+    let mut by_r: HashMap<&BigUint, (&BigUint, &Signature)> = HashMap::new();
+    for (h, sig) in sigs {
+        let Some(&(h1, sig1)) = by_r.get(&sig.r) else {
+            by_r.insert(&sig.r, (h, sig));
+            continue;
+        };
+        if sig1.s == sig.s {
+            // Same message signed twice, nothing to learn
+            continue;
+        }
+
+        let dh = (h1 % q + q - h % q) % q;
+        let ds = (&sig1.s + q - &sig.s) % q;
+        let Some(inv) = invmod(&ds, q) else {
+            continue;
+        };
+        let k = dh * inv % q;
+        match x_from_k(params, h, sig, &k) {
+            Some(x) if modexp(&params.g, &x, &params.p) == public.y => return Some(x),
+            _ => continue,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::RandBigInt;
+    use crate::s6c43::{hash, Params, PrivateKey};
+    use rand::thread_rng;
+
+    #[test]
+    fn challenge() {
+        let params = Params::challenge();
+        let private = PrivateKey::generate(&params);
+        let public = private.public();
+
+        let messages: Vec<String> = (0..11).map(|i| format!("Message number {}", i)).collect();
+        let mut rng = thread_rng();
+        let reused = rng.gen_biguint_below(&params.q);
+        let sigs: Vec<_> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| {
+                let sig = match i {
+                    3 | 8 => private.sign_with_k(msg.as_bytes(), &reused).unwrap(),
+                    _ => private.sign(msg.as_bytes()).unwrap(),
+                };
+                (hash(msg.as_bytes()), sig)
+            })
+            .collect();
+
+        let x = recover_key(&public, &sigs).unwrap();
+        assert_eq!(modexp(&params.g, &x, &params.p), public.y);
+
+        // No repeated nonce
+        let sigs: Vec<_> = sigs
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 8)
+            .map(|(_, sig)| sig)
+            .collect();
+        assert_eq!(recover_key(&public, &sigs), None);
+    }
+}
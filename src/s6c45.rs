@@ -0,0 +1,92 @@
+use crate::bignum::{invmod, modexp, BigUint, RandBigInt};
+use crate::s6c43::{Params, PublicKey, Signature};
+use rand::thread_rng;
+
+// Tampered parameters
+
+// With g = 0, every signature has r = 0, and a verifier that doesn't
+// check that 0 < r < q accepts r = 0 with any s for any message, since
+// v = g^u1 * y^u2 = 0.
+pub fn zero_g(params: &Params) -> Params {
+    Params {
+        g: BigUint::from(0u32),
+        ..params.clone()
+    }
+}
+
+// With g = p + 1, g^x = 1 mod p for any x, so the public key is 1 too,
+// but we can make a signature that works for any message against any
+// public key y, picking some z:
+// r = (y^z mod p) mod q
+// s = r / z mod q
+// Then u2 = r / s = z, and v = 1^u1 * y^z mod p mod q = r.
+pub fn p_plus_one_g(params: &Params) -> Params {
+    Params {
+        g: &params.p + 1u32,
+        ..params.clone()
+    }
+}
+
+pub fn magic_signature(public: &PublicKey) -> Option<Signature> {
+    let Params { p, q, .. } = &public.params;
+    let z = thread_rng().gen_biguint_range(&BigUint::from(1u32), q);
+    let r = modexp(&public.y, &z, p) % q;
+    let s = &r * invmod(&z, q)? % q;
+    Some(Signature { r, s })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s6c43::PrivateKey;
+
+    const MESSAGES: [&[u8]; 2] = [b"Hello, world", b"Goodbye, world"];
+
+    #[test]
+    fn g_is_zero() {
+        let params = zero_g(&Params::challenge());
+        let private = PrivateKey::generate(&params);
+        let public = private.public();
+
+        let sig = private
+            .sign_with_k(b"hi mom", &BigUint::from(12345u32))
+            .unwrap();
+        assert_eq!(sig.r, BigUint::from(0u32));
+        // r is always 0, so a proper signature can't be made
+        assert_eq!(private.sign(b"hi mom"), None);
+
+        let forged = Signature {
+            r: BigUint::from(0u32),
+            s: BigUint::from(42u32),
+        };
+        for msg in MESSAGES {
+            assert!(public.verify_unchecked(msg, &sig));
+            assert!(public.verify_unchecked(msg, &forged));
+            // A careful verifier is not fooled
+            assert!(!public.verify(msg, &forged));
+        }
+    }
+
+    #[test]
+    fn challenge() {
+        let params = p_plus_one_g(&Params::challenge());
+        let public = PrivateKey::generate(&params).public();
+        assert_eq!(public.y, BigUint::from(1u32));
+
+        let sig = magic_signature(&public).unwrap();
+        for msg in MESSAGES {
+            assert!(public.verify(msg, &sig));
+        }
+
+        // Works against any public key, as long as the verifier uses g
+        let real = PrivateKey::generate(&Params::challenge()).public();
+        let tampered = PublicKey {
+            params: params.clone(),
+            y: real.y,
+        };
+        let sig = magic_signature(&tampered).unwrap();
+        for msg in MESSAGES {
+            assert!(tampered.verify(msg, &sig));
+        }
+    }
+}
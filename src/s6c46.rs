@@ -0,0 +1,119 @@
+use crate::bignum::{from_bytes, modexp, to_bytes, BigUint, One};
+use crate::oracle::ValidityOracle;
+use crate::s5c39::PublicKey;
+
+// use a separate module for privacy
+mod oracle {
+    use crate::bignum::from_bytes;
+    use crate::oracle::ValidityOracle;
+    use crate::s5c39::{KeyPair, PublicKey};
+
+    pub struct Oracle {
+        pair: KeyPair,
+    }
+
+    impl Oracle {
+        pub fn new(bits: u64) -> Self {
+            Self {
+                pair: KeyPair::generate(bits, 65537).unwrap(),
+            }
+        }
+
+        pub fn public(&self) -> &PublicKey {
+            &self.pair.public
+        }
+    }
+
+    // "Valid" means the plaintext is even
+    impl ValidityOracle for Oracle {
+        fn is_valid(&self, ct: &[u8]) -> bool {
+            match self.pair.private.decrypt(&from_bytes(ct)) {
+                Ok(pt) => !pt.bit(0),
+                Err(_) => false,
+            }
+        }
+    }
+}
+
+pub use oracle::Oracle;
+
+// Multiplying the ciphertext by 2^e doubles the plaintext, mod n. Since
+// n is odd, 2m mod n is even iff 2m < n, i.e. m < n / 2: each query halves
+// the range m can be in.
+//
+// After i queries, m is in (lo * n / 2^i, hi * n / 2^i), where only the
+// integers lo and hi need to be tracked. Once 2^i > n the interval is
+// narrower than 1, and m = floor(hi * n / 2^i).
+//
+// progress gets the upper bound after each query, which converges to
+// the plaintext from the right.
+pub fn attack<T, F>(victim: &T, public: &PublicKey, ct: &[u8], mut progress: F) -> Vec<u8>
+where
+    T: ValidityOracle + ?Sized,
+    F: FnMut(&[u8]),
+{
+    let PublicKey { e, n } = public;
+    let k = public.size();
+    let double = modexp(&BigUint::from(2u32), e, n);
+
+    let mut c = from_bytes(ct);
+    let mut lo = BigUint::ZERO;
+    let mut hi = BigUint::one();
+    let mut denominator = BigUint::one();
+
+    for _ in 0..n.bits() {
+        c = c * &double % n;
+        lo <<= 1;
+        hi <<= 1;
+        denominator <<= 1;
+        let mid = (&lo + &hi) >> 1;
+
+        match victim.is_valid(&to_bytes(&c, k)) {
+            true => hi = mid,
+            false => lo = mid,
+        }
+        progress(&to_bytes(&(&hi * n / &denominator), 0));
+    }
+
+    to_bytes(&(hi * n / denominator), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oracle::QueryCounter;
+    use base64::prelude::*;
+
+    #[test]
+    fn parity() {
+        let victim = Oracle::new(512);
+        for m in 0u32..8 {
+            let c = victim.public().encrypt(&BigUint::from(m)).unwrap();
+            assert_eq!(victim.is_valid(&to_bytes(&c, 0)), m % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn challenge() {
+        let victim = Oracle::new(1024);
+        let b64 = "VGhhdCdzIHdoeSBJIGZvdW5kIHlvdSBkb24ndCBwbGF5IGFyb3VuZCB3aXRoIHRoZSBGdW5reSBDb2xkIE1lZGluYQ==";
+        let msg = BASE64_STANDARD.decode(b64).unwrap();
+        let c = victim.public().encrypt(&from_bytes(&msg)).unwrap();
+        let ct = to_bytes(&c, victim.public().size());
+
+        let counter = QueryCounter::new(&victim);
+        let mut steps = Vec::new();
+        let found = attack(&counter, victim.public(), &ct, |bound| {
+            steps.push(bound.to_vec())
+        });
+
+        assert_eq!(found, msg);
+        assert_eq!(counter.count(), 1024);
+        assert_eq!(steps.len(), 1024);
+        assert_eq!(steps.last(), Some(&msg));
+        // The upper bound only goes down
+        assert!(steps
+            .windows(2)
+            .all(|w| from_bytes(&w[1]) <= from_bytes(&w[0])));
+    }
+}
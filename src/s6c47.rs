@@ -0,0 +1,271 @@
+use crate::bignum::{from_bytes, modexp, to_bytes, BigUint, Integer, One};
+use crate::oracle::{QueryCounter, ValidityOracle};
+use crate::s5c39::{PublicKey, RsaError};
+use rand::{thread_rng, Rng};
+
+// PKCS#1 v1.5 encryption padding, k bytes long:
+// 00 02 PS 00 msg
+// where PS is at least 8 random non-zero bytes
+pub fn pad(msg: &[u8], k: usize) -> Result<Vec<u8>, RsaError> {
+    let ps_len = k
+        .checked_sub(msg.len() + 3)
+        .filter(|&len| len >= 8)
+        .ok_or(RsaError::MessageTooLarge)?;
+
+    let mut rng = thread_rng();
+    let mut em = vec![0x00, 0x02];
+    em.extend((0..ps_len).map(|_| rng.gen_range(1..=255u8)));
+    em.push(0x00);
+    em.extend_from_slice(msg);
+    Ok(em)
+}
+
+pub fn unpad(em: &[u8]) -> Option<Vec<u8>> {
+    let rest = em.strip_prefix(&[0x00, 0x02])?;
+    let ps_len = rest.iter().position(|&b| b == 0)?;
+    if ps_len < 8 {
+        return None;
+    }
+    Some(rest[ps_len + 1..].to_vec())
+}
+
+// use a separate module for privacy
+mod oracle {
+    use super::pad;
+    use crate::bignum::{from_bytes, to_bytes};
+    use crate::oracle::ValidityOracle;
+    use crate::s5c39::{KeyPair, PublicKey, RsaError};
+
+    pub struct Oracle {
+        pair: KeyPair,
+    }
+
+    impl Oracle {
+        pub fn new(bits: u64) -> Self {
+            Self {
+                pair: KeyPair::generate(bits, 3).unwrap(),
+            }
+        }
+
+        pub fn public(&self) -> &PublicKey {
+            &self.pair.public
+        }
+
+        pub fn encrypt(&self, msg: &[u8]) -> Result<Vec<u8>, RsaError> {
+            let public = self.public();
+            let m = from_bytes(&pad(msg, public.size())?);
+            Ok(to_bytes(&public.encrypt(&m)?, public.size()))
+        }
+    }
+
+    // Only checks the first two bytes: 00 02
+    impl ValidityOracle for Oracle {
+        fn is_valid(&self, ct: &[u8]) -> bool {
+            let Ok(m) = self.pair.private.decrypt(&from_bytes(ct)) else {
+                return false;
+            };
+            let em = to_bytes(&m, self.public().size());
+            em.starts_with(&[0x00, 0x02])
+        }
+    }
+}
+
+pub use oracle::Oracle;
+
+#[derive(Debug)]
+pub struct Recovered {
+    // The whole padded message
+    pub padded: Vec<u8>,
+    pub queries: usize,
+}
+
+// Sorted, with no overlap
+fn merge(mut intervals: Vec<(BigUint, BigUint)>) -> Vec<(BigUint, BigUint)> {
+    intervals.sort();
+    let mut merged: Vec<(BigUint, BigUint)> = Vec::with_capacity(intervals.len());
+    for (a, b) in intervals {
+        match merged.last_mut() {
+            Some((_, last_b)) if a <= &*last_b + 1u32 => {
+                if b > *last_b {
+                    *last_b = b;
+                }
+            }
+            _ => merged.push((a, b)),
+        }
+    }
+    merged
+}
+
+// Bleichenbacher's 1998 attack, see "Chosen Ciphertext Attacks Against
+// Protocols Based on the RSA Encryption Standard PKCS #1".
+//
+// ct must be PKCS conforming already, so step 1 (blinding) is skipped;
+// if it isn't, give up right away. Give up as well after max_queries
+// queries to the victim.
+//
+// With B = 2^(8(k - 2)), a conforming plaintext is in [2B, 3B). We look
+// for multipliers s such that m * s is conforming too, and each one
+// narrows down the intervals m can be in, until there's a single value
+// left.
+pub fn attack<T: ValidityOracle + ?Sized>(
+    victim: &T,
+    public: &PublicKey,
+    ct: &[u8],
+    max_queries: usize,
+) -> Option<Recovered> {
+    let victim = QueryCounter::new(victim);
+    let PublicKey { e, n } = public;
+    let k = public.size();
+    let c0 = from_bytes(ct);
+
+    // Too small to hold 00 02, 8 bytes of PS and 00
+    if k < 11 {
+        return None;
+    }
+
+    let b = BigUint::one() << (8 * (k - 2));
+    let b2 = &b * 2u32;
+    let b3 = &b * 3u32;
+
+    // None once we're out of queries
+    let conforming = |s: &BigUint| {
+        if victim.count() >= max_queries {
+            return None;
+        }
+        let c = &c0 * modexp(s, e, n) % n;
+        Some(victim.is_valid(&to_bytes(&c, k)))
+    };
+    // Smallest s >= from that works
+    let search = |from: BigUint| {
+        let mut s = from;
+        while !conforming(&s)? {
+            s += 1u32;
+        }
+        Some(s)
+    };
+
+    if !conforming(&BigUint::one())? {
+        return None;
+    }
+    let mut intervals = vec![(b2.clone(), &b3 - 1u32)];
+
+    // Step 2a
+    let mut s = search(n.div_ceil(&b3))?;
+
+    loop {
+        // Step 3: narrow down the intervals
+        let mut narrowed = Vec::new();
+        for (a, b) in &intervals {
+            let r_min = (a * &s + 1u32 - &b3).div_ceil(n);
+            let r_max = (b * &s - &b2) / n;
+            let mut r = r_min;
+            while r <= r_max {
+                let lo = (&b2 + &r * n).div_ceil(&s).max(a.clone());
+                let hi = ((&b3 - 1u32 + &r * n) / &s).min(b.clone());
+                if lo <= hi {
+                    narrowed.push((lo, hi));
+                }
+                r += 1u32;
+            }
+        }
+        intervals = merge(narrowed);
+
+        // Step 4: done?
+        match &intervals[..] {
+            [] => return None,
+            [(a, b)] if a == b => {
+                return Some(Recovered {
+                    padded: to_bytes(a, k),
+                    queries: victim.count(),
+                });
+            }
+            // Step 2c: only one interval left, search for s in a way that
+            // roughly halves it each time
+            [(a, b)] => {
+                let mut r = (b * &s * 2u32 - &b2 * 2u32).div_ceil(n);
+                s = 'found: loop {
+                    let s_min = (&b2 + &r * n).div_ceil(b);
+                    let s_max = (&b3 + &r * n).div_ceil(a);
+                    let mut candidate = s_min;
+                    while candidate < s_max {
+                        if conforming(&candidate)? {
+                            break 'found candidate;
+                        }
+                        candidate += 1u32;
+                    }
+                    r += 1u32;
+                };
+            }
+            // Step 2b: several intervals, just keep looking
+            _ => s = search(s + 1u32)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding() {
+        let em = pad(b"kick it, CC", 32).unwrap();
+        assert_eq!(em.len(), 32);
+        assert!(em[2..20].iter().all(|&b| b != 0));
+        assert_eq!(unpad(&em), Some(b"kick it, CC".to_vec()));
+
+        assert_eq!(pad(&[0; 22], 32), Err(RsaError::MessageTooLarge));
+        assert!(pad(&[0; 21], 32).is_ok());
+        assert_eq!(unpad(&[0, 2, 1, 1, 0, 42]), None);
+    }
+
+    // Step 2a takes about n / B queries, a few times 2^16 at worst, and
+    // the rest a few thousand more
+    const MAX_QUERIES: usize = 1 << 20;
+
+    fn recover(bits: u64) {
+        let victim = Oracle::new(bits);
+        let ct = victim.encrypt(b"kick it, CC").unwrap();
+        assert!(victim.is_valid(&ct));
+
+        let counter = QueryCounter::new(&victim);
+        let found = attack(&counter, victim.public(), &ct, MAX_QUERIES).unwrap();
+        assert_eq!(found.queries, counter.count());
+        assert!(found.queries <= MAX_QUERIES);
+        assert_eq!(unpad(&found.padded), Some(b"kick it, CC".to_vec()));
+    }
+
+    #[test]
+    fn challenge() {
+        recover(256);
+    }
+
+    // Challenge 48: same with a bigger key, where we go through step 2b
+    #[test]
+    fn challenge_768() {
+        recover(768);
+    }
+
+    #[test]
+    fn give_up() {
+        let victim = Oracle::new(256);
+        let ct = victim.encrypt(b"kick it, CC").unwrap();
+
+        // Not conforming to begin with: a single query to find out
+        let bad = vec![0; ct.len()];
+        let counter = QueryCounter::new(&victim);
+        assert!(attack(&counter, victim.public(), &bad, MAX_QUERIES).is_none());
+        assert_eq!(counter.count(), 1);
+
+        // Out of queries
+        let counter = QueryCounter::new(&victim);
+        assert!(attack(&counter, victim.public(), &ct, 100).is_none());
+        assert_eq!(counter.count(), 100);
+
+        // Too small for PKCS#1 v1.5
+        let tiny = PublicKey {
+            e: BigUint::from(3u32),
+            n: BigUint::from(u64::MAX),
+        };
+        assert!(attack(&victim, &tiny, &ct[..8], MAX_QUERIES).is_none());
+    }
+}
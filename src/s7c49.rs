@@ -0,0 +1,308 @@
+use crate::s1c02::xor_buf;
+use crate::s2c09::pkcs7_pad;
+use crate::s2c10::aes_128_cbc_encrypt;
+use crate::s2c13::kv;
+
+pub const BLOCK_SIZE: usize = 16;
+
+// Last block of the AES-128-CBC encryption of the PKCS#7 padded message
+pub fn cbc_mac(key: &[u8], iv: &[u8], msg: &[u8]) -> Option<[u8; BLOCK_SIZE]> {
+    let ct = aes_128_cbc_encrypt(key, iv, &pkcs7_pad(msg, BLOCK_SIZE))?;
+    ct[ct.len() - BLOCK_SIZE..].try_into().ok()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+}
+
+// "from=#{from_id}&tx_list=#{transactions}", where transactions look like
+// "to:amount;to:amount". Everything after "tx_list=" is the list, and
+// entries that don't make sense are skipped.
+pub fn parse_tx_list(msg: &[u8]) -> Option<Vec<Transfer>> {
+    let rest = msg.strip_prefix(b"from=")?;
+    let end = rest.iter().position(|&b| b == b'&')?;
+    let from = std::str::from_utf8(&rest[..end]).ok()?;
+    let list = rest[end + 1..].strip_prefix(b"tx_list=")?;
+
+    let transfers = list
+        .split(|&b| b == b';')
+        .filter_map(|entry| {
+            let entry = std::str::from_utf8(entry).ok()?;
+            let (to, amount) = entry.split_once(':')?;
+            Some(Transfer {
+                from: from.to_string(),
+                to: to.to_string(),
+                amount: amount.parse().ok()?,
+            })
+        })
+        .collect();
+    Some(transfers)
+}
+
+// use a separate module for privacy
+mod oracle {
+    use super::{cbc_mac, parse_tx_list, Transfer, BLOCK_SIZE};
+    use crate::consttime::ct_eq;
+    use crate::s2c13::kv;
+    use rand::{thread_rng, Rng};
+
+    // The API server, and the web client that signs requests for the user
+    // that's logged in. The two share a key.
+    pub struct Server {
+        key: [u8; BLOCK_SIZE],
+    }
+
+    impl Server {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            Self {
+                key: thread_rng().gen(),
+            }
+        }
+
+        // Version 1: message || IV || MAC, with a random IV picked by the
+        // client
+        pub fn transfer_request(&self, from: &str, to: &str, amount: u64) -> Vec<u8> {
+            let amount = amount.to_string();
+            let msg = kv::encode(&[("from", from), ("to", to), ("amount", &amount)]);
+            let iv: [u8; BLOCK_SIZE] = thread_rng().gen();
+
+            let mac = cbc_mac(&self.key, &iv, msg.as_bytes()).unwrap();
+            [msg.as_bytes(), &iv, &mac].concat()
+        }
+
+        pub fn process_transfer(&self, request: &[u8]) -> Option<Transfer> {
+            let msg_len = request.len().checked_sub(2 * BLOCK_SIZE)?;
+            let (msg, rest) = request.split_at(msg_len);
+            let (iv, mac) = rest.split_at(BLOCK_SIZE);
+            if !ct_eq(&cbc_mac(&self.key, iv, msg)?, mac) {
+                return None;
+            }
+
+            let pairs = kv::parse(std::str::from_utf8(msg).ok()?)?;
+            Some(Transfer {
+                from: kv::get(&pairs, "from")?.to_string(),
+                to: kv::get(&pairs, "to")?.to_string(),
+                amount: kv::get(&pairs, "amount")?.parse().ok()?,
+            })
+        }
+
+        // Version 2: message || MAC, with a fixed all-zero IV
+        pub fn tx_list_request(&self, from: &str, transactions: &[(&str, u64)]) -> Vec<u8> {
+            let list: Vec<_> = transactions
+                .iter()
+                .map(|(to, amount)| format!("{}:{}", to, amount))
+                .collect();
+            let msg = format!("from={}&tx_list={}", from, list.join(";"));
+
+            let mut request = msg.into_bytes();
+            request.extend_from_slice(&cbc_mac(&self.key, &[0; BLOCK_SIZE], &request).unwrap());
+            request
+        }
+
+        pub fn process_tx_list(&self, request: &[u8]) -> Option<Vec<Transfer>> {
+            let msg_len = request.len().checked_sub(BLOCK_SIZE)?;
+            let (msg, mac) = request.split_at(msg_len);
+            if !ct_eq(&cbc_mac(&self.key, &[0; BLOCK_SIZE], msg)?, mac) {
+                return None;
+            }
+            parse_tx_list(msg)
+        }
+    }
+}
+
+pub use oracle::Server;
+
+// Version 1: the first block of the message only goes through the IV, so
+// changing the "from" field and flipping the same bits in the IV keeps
+// the MAC valid.
+//
+// The victim's id must be the same length as ours, and still fit in the
+// first block.
+pub fn forge_transfer(request: &[u8], victim: &str) -> Option<Vec<u8>> {
+    let msg_len = request.len().checked_sub(2 * BLOCK_SIZE)?;
+    let (msg, rest) = request.split_at(msg_len);
+    let (iv, mac) = rest.split_at(BLOCK_SIZE);
+
+    let pairs = kv::parse(std::str::from_utf8(msg).ok()?)?;
+    let own = kv::get(&pairs, "from")?;
+    let prefix = format!("from={}", kv::escape(own));
+    let forged_prefix = format!("from={}", kv::escape(victim));
+    if prefix.len() != forged_prefix.len()
+        || prefix.len() > BLOCK_SIZE
+        || !msg.starts_with(prefix.as_bytes())
+    {
+        return None;
+    }
+
+    let mut forged_msg = msg.to_vec();
+    forged_msg[..prefix.len()].copy_from_slice(forged_prefix.as_bytes());
+    let diff = xor_buf(msg.get(..BLOCK_SIZE)?, forged_msg.get(..BLOCK_SIZE)?)?;
+
+    let mut forged = forged_msg;
+    forged.extend(xor_buf(iv, &diff)?);
+    forged.extend_from_slice(mac);
+    Some(forged)
+}
+
+// Version 2: length extension. The MAC of the captured message is the
+// CBC state after it (and its padding), so appending our own message with
+// its first block XORed with that MAC brings the state back to where it
+// is after our first block, and the MAC of the whole thing is our MAC.
+//
+// The captured message's padding and the first block of our message,
+// which turns into 16 bytes of garbage, land in the last entry of the
+// captured list, so the last transfer of the captured list is lost:
+// there's no way around it, since the padding always comes right after
+// it.
+//
+// The garbage is fixed by our id and the captured MAC, and can contain
+// ';', which splits it into entries of its own. The first one has the
+// padding in its amount, so it never parses. The last one runs into the
+// first entry of our list, so make that a throwaway entry with a ':' in
+// the recipient: it never parses either, whatever the garbage. The ones
+// in between are pure garbage: in the unlikely case one of them parses,
+// give up rather than send a transfer we didn't ask for.
+pub fn forge_tx_list(captured: &[u8], own: &[u8]) -> Option<Vec<u8>> {
+    let (captured_msg, captured_mac) = captured.split_at(captured.len().checked_sub(BLOCK_SIZE)?);
+    let (own_msg, own_mac) = own.split_at(own.len().checked_sub(BLOCK_SIZE)?);
+    if own_msg.len() < BLOCK_SIZE {
+        return None;
+    }
+
+    let mut forged = pkcs7_pad(captured_msg, BLOCK_SIZE);
+    forged.extend(xor_buf(&own_msg[..BLOCK_SIZE], captured_mac)?);
+    forged.extend_from_slice(&own_msg[BLOCK_SIZE..]);
+
+    // Exactly the captured transfers but the last, then ours
+    let entries = |transfers: Vec<Transfer>| -> Vec<(String, u64)> {
+        transfers.into_iter().map(|t| (t.to, t.amount)).collect()
+    };
+    let mut expected = entries(parse_tx_list(captured_msg)?);
+    expected.pop();
+    expected.extend(entries(parse_tx_list(own_msg)?));
+    if entries(parse_tx_list(&forged)?) != expected {
+        return None;
+    }
+
+    forged.extend_from_slice(own_mac);
+    Some(forged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac() {
+        let key = b"YELLOW SUBMARINE";
+        let iv = [0; BLOCK_SIZE];
+        let mac = cbc_mac(key, &iv, b"hello").unwrap();
+        assert_ne!(cbc_mac(key, &iv, b"hellp").unwrap(), mac);
+        assert_ne!(cbc_mac(key, &[1; BLOCK_SIZE], b"hello").unwrap(), mac);
+        assert_eq!(cbc_mac(key, &[0; 8], b"hello"), None);
+    }
+
+    #[test]
+    fn tx_list() {
+        let transfers =
+            parse_tx_list(b"from=alice&tx_list=bob:10;\xff:3;carol;dave:x;eve:7").unwrap();
+        let got: Vec<_> = transfers
+            .iter()
+            .map(|t| (t.from.as_str(), t.to.as_str(), t.amount))
+            .collect();
+        assert_eq!(got, [("alice", "bob", 10), ("alice", "eve", 7)]);
+        assert_eq!(parse_tx_list(b"to=alice&tx_list=bob:10"), None);
+    }
+
+    #[test]
+    fn attacker_iv() {
+        let server = Server::new();
+        let request = server.transfer_request("mallory", "mallory", 1_000_000);
+        let transfer = server.process_transfer(&request).unwrap();
+        assert_eq!(transfer.from, "mallory");
+
+        let mut tampered = request.clone();
+        tampered[5] ^= 1;
+        assert_eq!(server.process_transfer(&tampered), None);
+
+        let forged = forge_transfer(&request, "alice12").unwrap();
+        let transfer = server.process_transfer(&forged).unwrap();
+        assert_eq!(
+            transfer,
+            Transfer {
+                from: "alice12".to_string(),
+                to: "mallory".to_string(),
+                amount: 1_000_000,
+            }
+        );
+
+        assert_eq!(forge_transfer(&request, "alice"), None);
+
+        // Shorter than a block
+        let short = [&b"from=mallory"[..], &[0; 2 * BLOCK_SIZE]].concat();
+        assert_eq!(forge_transfer(&short, "alice12"), None);
+    }
+
+    #[test]
+    fn fixed_iv() {
+        let server = Server::new();
+        let captured = server.tx_list_request("alice", &[("bob", 10), ("carol", 25)]);
+        assert_eq!(server.process_tx_list(&captured).unwrap().len(), 2);
+
+        let own = server.tx_list_request("mallory", &[("mallory:", 0), ("mallory", 1_000_000)]);
+        let forged = forge_tx_list(&captured, &own).unwrap();
+        let transfers = server.process_tx_list(&forged).unwrap();
+
+        // Carol's transfer is merged with the padding and garbage, and
+        // dropped along with our throwaway entry, even if the garbage has
+        // a ';' in it
+        let got: Vec<_> = transfers
+            .iter()
+            .map(|t| (t.from.as_str(), t.to.as_str(), t.amount))
+            .collect();
+        assert_eq!(got, [("alice", "bob", 10), ("alice", "mallory", 1_000_000)]);
+    }
+    // Same as the server, with a key we know, so that we can pick a
+    // captured message whose MAC puts a ';' in the garbage
+    #[test]
+    fn garbage_separator() {
+        let key = b"YELLOW SUBMARINE";
+        let sign = |msg: &str| {
+            let mut request = msg.as_bytes().to_vec();
+            request.extend(cbc_mac(key, &[0; BLOCK_SIZE], msg.as_bytes()).unwrap());
+            request
+        };
+        let own = sign("from=mallory&tx_list=mallory::0;mallory:1000000");
+
+        let mut tried = 0;
+        for amount in 0.. {
+            let captured = sign(&format!("from=alice&tx_list=bob:10;carol:{}", amount));
+            let garbage = xor_buf(&own[..BLOCK_SIZE], &captured[captured.len() - BLOCK_SIZE..]);
+            if !garbage.unwrap().contains(&b';') {
+                continue;
+            }
+
+            let forged = forge_tx_list(&captured, &own).unwrap();
+            let (msg, mac) = forged.split_at(forged.len() - BLOCK_SIZE);
+            assert_eq!(cbc_mac(key, &[0; BLOCK_SIZE], msg).unwrap(), mac);
+            let got: Vec<_> = parse_tx_list(msg)
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.to, t.amount))
+                .collect();
+            assert_eq!(
+                got,
+                [("bob".to_string(), 10), ("mallory".to_string(), 1_000_000)]
+            );
+
+            tried += 1;
+            if tried == 10 {
+                break;
+            }
+        }
+    }
+}
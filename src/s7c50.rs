@@ -0,0 +1,60 @@
+use crate::s1c02::xor_buf;
+use crate::s1c07::aes_128_ecb_decrypt;
+use crate::s2c10::aes_128_cbc_encrypt;
+use crate::s7c49::{cbc_mac, BLOCK_SIZE};
+
+pub const KEY: &[u8; BLOCK_SIZE] = b"YELLOW SUBMARINE";
+
+// CBC-MAC with a known key and a zero IV, used as a hash function
+pub fn hash(msg: &[u8]) -> [u8; BLOCK_SIZE] {
+    cbc_mac(KEY, &[0; BLOCK_SIZE], msg).unwrap()
+}
+
+// Since the key is public, we can run the cipher backwards. Start with
+// the code, then "//" to comment out the rest of the line, and pad that
+// to a whole number of blocks. One more block X brings the CBC state to
+// whatever we want, and the PKCS#7 padding adds a whole block:
+// target = E(E(state ^ X) ^ pad)
+// so X = D(D(target) ^ pad) ^ state
+//
+// X is garbage, and it stays in the comment as long as it doesn't have a
+// line break. If it does, change the filler in the comment and try again.
+pub fn collide(code: &str, target: &[u8; BLOCK_SIZE]) -> Vec<u8> {
+    let pad = [BLOCK_SIZE as u8; BLOCK_SIZE];
+    let before_pad = aes_128_ecb_decrypt(KEY, target).unwrap();
+    let wanted = aes_128_ecb_decrypt(KEY, &xor_buf(&before_pad, &pad).unwrap()).unwrap();
+
+    let mut attempt = 0u64;
+    loop {
+        let mut forged = format!("{}//{}", code, attempt).into_bytes();
+        forged.resize(forged.len().next_multiple_of(BLOCK_SIZE), b' ');
+
+        // The CBC state is the MAC without the padding block
+        let ct = aes_128_cbc_encrypt(KEY, &[0; BLOCK_SIZE], &forged).unwrap();
+        let state = &ct[ct.len() - BLOCK_SIZE..];
+        let x = xor_buf(&wanted, state).unwrap();
+        if x.iter().all(|&b| b != b'\n' && b != b'\r') {
+            forged.extend(x);
+            return forged;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge() {
+        let original = b"alert('MZA who was that?');\n";
+        let target = hash(original);
+        assert_eq!(hex::encode(target), "296b8d7cb78a243dda4d0a61d33bbdd1");
+
+        let code = "alert('Ayo, the Wu is back!');";
+        let forged = collide(code, &target);
+        assert_eq!(hash(&forged), target);
+        assert!(forged.starts_with(b"alert('Ayo, the Wu is back!');//"));
+        assert!(!forged.contains(&b'\n'));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::oracle::{LengthOracle, QueryCounter};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    Ctr,
+    Cbc,
+}
+
+pub fn format_request(session_id: &str, content: &[u8]) -> Vec<u8> {
+    let mut request = format!(
+        "POST / HTTP/1.1\n\
+         Host: hapless.com\n\
+         Cookie: sessionid={}\n\
+         Content-Length: {}\n",
+        session_id,
+        content.len()
+    )
+    .into_bytes();
+    request.extend_from_slice(content);
+    request
+}
+
+// use a separate module for privacy
+mod oracle {
+    use super::{format_request, Mode};
+    use crate::oracle::LengthOracle;
+    use crate::s2c09::pkcs7_pad;
+    use crate::s2c10::aes_128_cbc_encrypt;
+    use crate::s3c18::aes_128_ctr;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use rand::{thread_rng, Rng};
+    use std::io::Write;
+
+    pub struct Oracle {
+        session_id: String,
+        mode: Mode,
+    }
+
+    impl Oracle {
+        pub fn new(session_id: &str, mode: Mode) -> Self {
+            Self {
+                session_id: session_id.to_string(),
+                mode,
+            }
+        }
+    }
+
+    // Compress, then encrypt with a fresh key (and nonce or IV) every time
+    impl LengthOracle for Oracle {
+        fn length(&self, input: &[u8]) -> usize {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&format_request(&self.session_id, input))
+                .unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let mut rng = thread_rng();
+            let key: [u8; 16] = rng.gen();
+            let ct = match self.mode {
+                Mode::Ctr => aes_128_ctr(&key, rng.gen(), &compressed),
+                Mode::Cbc => {
+                    let iv: [u8; 16] = rng.gen();
+                    aes_128_cbc_encrypt(&key, &iv, &pkcs7_pad(&compressed, 16))
+                }
+            };
+            ct.unwrap().len()
+        }
+    }
+}
+
+pub use oracle::Oracle;
+
+#[derive(Debug)]
+pub struct Recovered {
+    pub session_id: String,
+    pub queries: usize,
+}
+
+// What the session id can be made of: base64, and the line break that
+// ends it
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=\n";
+
+// Never appears in the request, and doesn't compress well: bytes that
+// don't repeat, starting at base, for steps of about a byte. Then a run
+// of the same byte, which adds a few bits at a time as it gets longer.
+#[derive(Clone, Copy)]
+struct Filler {
+    base: u8,
+    repeat: u8,
+}
+
+// Different bytes get different Huffman codes, so if a guess is hidden
+// with one filler, it may well show with another
+const FILLERS: [Filler; 2] = [
+    Filler {
+        base: 0x80,
+        repeat: b'~',
+    },
+    Filler {
+        base: 0xc0,
+        repeat: b'|',
+    },
+];
+
+impl Filler {
+    fn make(self, distinct: u8, run: usize) -> Vec<u8> {
+        let mut filler: Vec<u8> = (self.base..self.base + distinct).collect();
+        filler.extend(std::iter::repeat_n(self.repeat, run));
+        filler
+    }
+}
+
+// Never appears in the request either, and keeps the guess from joining
+// the repeat
+const SEPARATOR: &[u8] = b"!@#";
+
+// If the guess is right, "sessionid=" followed by what we know and the
+// guess repeats the real cookie, and compresses differently than with the
+// guess moved after a separator. If it's wrong, both have the same
+// literals in a different order, and compress to exactly the same size.
+//
+// The difference is a few bits, and only shows up when it pushes the
+// output across a byte boundary, or with CBC, a block boundary. So
+// prepend more and more filler, and whenever the request without a guess
+// crosses a boundary, try every guess on each side of it: that's where
+// the right guess can end up in a different block than its counterpart.
+fn next_byte<T: LengthOracle + ?Sized>(victim: &T, known: &[u8]) -> Option<u8> {
+    let context = [&b"sessionid="[..], known].concat();
+    FILLERS
+        .iter()
+        .find_map(|&filler| (0..3).find_map(|skip| sweep(victim, filler, &context[skip..])))
+}
+
+// Going through the fillers with a given context before the guess.
+//
+// Some match lengths cost more bits than others, so the right guess can
+// save almost nothing with a context of a given length, or be hidden by
+// how the filler compresses: the caller tries again with a shorter
+// context, then with another filler.
+fn sweep<T: LengthOracle + ?Sized>(victim: &T, filler: Filler, context: &[u8]) -> Option<u8> {
+    let length = |filler: &[u8], parts: &[&[u8]]| {
+        let mut input = filler.to_vec();
+        input.extend_from_slice(context);
+        input.extend(parts.concat());
+        victim.length(&input)
+    };
+    let guess = |filler: &[u8]| {
+        let mut differ = ALPHABET
+            .iter()
+            .filter(|&&c| length(filler, &[&[c], SEPARATOR]) != length(filler, &[SEPARATOR, &[c]]));
+        match (differ.next(), differ.next()) {
+            (Some(&c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    let mut prev: Option<(usize, Vec<u8>)> = None;
+    let mut tested = None;
+    for distinct in 0..48 {
+        for run in 0..16 {
+            let filler = filler.make(distinct, run);
+            let probe = length(&filler, &[SEPARATOR]);
+            let candidates = match &prev {
+                None => vec![filler.clone()],
+                Some((prev_probe, prev_filler)) if *prev_probe != probe => {
+                    vec![prev_filler.clone(), filler.clone()]
+                }
+                Some(_) => vec![],
+            };
+            for candidate in candidates {
+                if tested.as_ref() == Some(&candidate) {
+                    continue;
+                }
+                if let Some(c) = guess(&candidate) {
+                    return Some(c);
+                }
+                tested = Some(candidate);
+            }
+            prev = Some((probe, filler));
+        }
+    }
+    None
+}
+
+// Recover the session id up to the end of its line, at most max_len bytes
+pub fn attack<T: LengthOracle + ?Sized>(victim: &T, max_len: usize) -> Option<Recovered> {
+    let victim = QueryCounter::new(victim);
+    let mut known = Vec::new();
+    while known.len() < max_len {
+        match next_byte(&victim, &known)? {
+            b'\n' => break,
+            c => known.push(c),
+        }
+    }
+
+    Some(Recovered {
+        session_id: String::from_utf8(known).ok()?,
+        queries: victim.count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::*;
+    use rand::{thread_rng, Rng};
+
+    const SESSION_ID: &str = "TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=";
+
+    #[test]
+    fn lengths() {
+        let ctr = Oracle::new(SESSION_ID, Mode::Ctr);
+        let cbc = Oracle::new(SESSION_ID, Mode::Cbc);
+        for input in [&b""[..], b"hello", &[b'a'; 100]] {
+            let ctr_len = ctr.length(input);
+            assert_eq!(ctr_len, ctr.length(input));
+            assert_eq!(cbc.length(input), (ctr_len / 16 + 1) * 16);
+        }
+        for filler in FILLERS {
+            assert!(ctr.length(&filler.make(48, 0)) > ctr.length(&[b'a'; 48]));
+        }
+    }
+
+    #[test]
+    fn challenge_ctr() {
+        let victim = Oracle::new(SESSION_ID, Mode::Ctr);
+        let found = attack(&victim, 64).unwrap();
+        assert_eq!(found.session_id, SESSION_ID);
+    }
+
+    #[test]
+    fn challenge_cbc() {
+        let victim = Oracle::new(SESSION_ID, Mode::Cbc);
+        let found = attack(&victim, 64).unwrap();
+        assert_eq!(found.session_id, SESSION_ID);
+    }
+
+    #[test]
+    fn random_session_ids() {
+        let mut rng = thread_rng();
+        for mode in [Mode::Ctr, Mode::Cbc, Mode::Ctr, Mode::Cbc] {
+            let session_id = BASE64_STANDARD.encode(rng.gen::<[u8; 24]>());
+            let victim = Oracle::new(&session_id, mode);
+            let found = attack(&victim, 64).unwrap();
+            assert_eq!(found.session_id, session_id);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::s1c07::aes_128_ecb_encrypt;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+
+pub const BLOCK_SIZE: usize = 16;
+
+pub type Block = [u8; BLOCK_SIZE];
+
+// The chaining value, of which only the low bits are used
+pub type State = u32;
+
+// A deliberately weak Merkle-Damgård hash, with AES-128 as the compression
+// function: each message block is the key used to encrypt the state, and
+// the next state is the result truncated to a few bits.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Md {
+    bits: u32,
+}
+
+impl Md {
+    // Between 8 and 32 bits
+    pub fn new(bits: u32) -> Self {
+        assert!((8..=32).contains(&bits));
+        Self { bits }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn initial(&self) -> State {
+        0x0123_4567 & self.mask()
+    }
+
+    fn mask(&self) -> State {
+        State::MAX >> (32 - self.bits)
+    }
+
+    pub fn compress(&self, state: State, block: &Block) -> State {
+        let mut pt = [0; BLOCK_SIZE];
+        pt[..4].copy_from_slice(&state.to_be_bytes());
+        let ct = aes_128_ecb_encrypt(block, &pt).unwrap();
+        State::from_be_bytes(ct[..4].try_into().unwrap()) & self.mask()
+    }
+
+    // No padding: data must be a whole number of blocks
+    pub fn iterate(&self, state: State, data: &[u8]) -> State {
+        assert!(data.len().is_multiple_of(BLOCK_SIZE));
+        data.chunks_exact(BLOCK_SIZE).fold(state, |state, block| {
+            self.compress(state, block.try_into().unwrap())
+        })
+    }
+
+    // Padding for a message of len bytes, with its length so that
+    // messages of different lengths don't collide trivially:
+    // 0x80, then zeroes, then the length in bits as a 64-bit big-endian
+    // integer
+    pub fn padding(&self, len: usize) -> Vec<u8> {
+        let mut pad = vec![0x80];
+        let zeroes = (BLOCK_SIZE - (len + 1 + 8) % BLOCK_SIZE) % BLOCK_SIZE;
+        pad.resize(1 + zeroes, 0);
+        pad.extend_from_slice(&(8 * len as u64).to_be_bytes());
+        pad
+    }
+
+    pub fn hash(&self, msg: &[u8]) -> State {
+        let mut padded = msg.to_vec();
+        padded.extend(self.padding(msg.len()));
+        self.iterate(self.initial(), &padded)
+    }
+}
+
+// Two different blocks that take the two states to the same one, by the
+// birthday paradox: about 2^(bits/2) compressions
+pub fn find_collision(md: &Md, from: (State, State)) -> (Block, Block, State) {
+    let starts = [from.0, from.1];
+    // From a single state, any two blocks will do
+    let table = |side: usize| if from.0 == from.1 { 0 } else { side };
+
+    let mut rng = thread_rng();
+    let mut seen: [HashMap<State, Block>; 2] = Default::default();
+    loop {
+        for side in 0..2 {
+            let block: Block = rng.gen();
+            let state = md.compress(starts[side], &block);
+            match seen[table(1 - side)].get(&state) {
+                Some(&other) if other != block => {
+                    return match side {
+                        0 => (block, other, state),
+                        _ => (other, block, state),
+                    };
+                }
+                _ => {
+                    seen[table(side)].insert(state, block);
+                }
+            }
+        }
+    }
+}
+
+// n collisions in a row, which make 2^n messages of n blocks that all
+// end up in the same state: Joux multicollisions
+#[derive(Debug)]
+pub struct Multicollision {
+    pub pairs: Vec<(Block, Block)>,
+    pub state: State,
+}
+
+impl Multicollision {
+    pub fn find(md: &Md, state: State, n: usize) -> Self {
+        let mut pairs = Vec::with_capacity(n);
+        let mut state = state;
+        for _ in 0..n {
+            let (a, b, next) = find_collision(md, (state, state));
+            pairs.push((a, b));
+            state = next;
+        }
+        Self { pairs, state }
+    }
+
+    // Message number i picks the second block of pair j if bit j of i is
+    // set
+    pub fn message(&self, i: usize) -> Vec<u8> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .flat_map(|(j, (a, b))| {
+                let bit = i.checked_shr(j as u32).unwrap_or(0) & 1;
+                if bit == 0 {
+                    *a
+                } else {
+                    *b
+                }
+            })
+            .collect()
+    }
+
+    // With more pairs than bits in a usize, only the first ones vary, which
+    // still makes more messages than anyone can go through
+    pub fn messages(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let last = u32::try_from(self.pairs.len())
+            .ok()
+            .and_then(|n| 1usize.checked_shl(n))
+            .map_or(usize::MAX, |count| count - 1);
+        (0..=last).map(|i| self.message(i))
+    }
+}
+
+// Two messages that collide in both f and g, so in the cascade
+// h(x) = f(x) || g(x) too, even if g is the stronger of the two.
+//
+// Find 2^(g bits / 2) messages colliding in f, which takes only that
+// many times more work than a single collision in f, and hope two of them
+// collide in g as well. If not, add one more block of collisions in f,
+// and try again.
+pub fn cascade_collision(f: &Md, g: &Md) -> (Vec<u8>, Vec<u8>) {
+    let n = g.bits().div_ceil(2) as usize;
+    let mut collisions = Multicollision::find(f, f.initial(), n);
+    loop {
+        let mut seen = HashMap::new();
+        for msg in collisions.messages() {
+            if let Some(other) = seen.insert(g.hash(&msg), msg.clone()) {
+                return (other, msg);
+            }
+        }
+
+        let (a, b, state) = find_collision(f, (collisions.state, collisions.state));
+        collisions.pairs.push((a, b));
+        collisions.state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash() {
+        let md = Md::new(16);
+        assert!(md.hash(b"hello") < 1 << 16);
+        assert_eq!(md.hash(b"hello"), md.hash(b"hello"));
+
+        for len in 0..40 {
+            assert_eq!((len + md.padding(len).len()) % BLOCK_SIZE, 0);
+            assert!(md.padding(len).len() >= 9);
+        }
+        assert_eq!(md.padding(7).len(), 9);
+        assert_eq!(md.padding(8).len(), 24);
+    }
+
+    #[test]
+    fn multicollision() {
+        let md = Md::new(16);
+        let collisions = Multicollision::find(&md, md.initial(), 5);
+        let messages: Vec<_> = collisions.messages().collect();
+        assert_eq!(messages.len(), 32);
+
+        let hash = md.hash(&messages[0]);
+        for (i, msg) in messages.iter().enumerate() {
+            assert_eq!(md.iterate(md.initial(), msg), collisions.state);
+            assert_eq!(md.hash(msg), hash);
+            assert!(messages[..i].iter().all(|other| other != msg));
+        }
+    }
+
+    // Not real collisions, just numbering
+    #[test]
+    fn many_pairs() {
+        let pairs: Vec<_> = (0..70)
+            .map(|j| ([j; BLOCK_SIZE], [j + 100; BLOCK_SIZE]))
+            .collect();
+        let collisions = Multicollision { pairs, state: 0 };
+
+        let messages: Vec<_> = collisions.messages().take(3).collect();
+        assert_eq!(messages[0], collisions.message(0));
+        assert_eq!(messages[2][BLOCK_SIZE..2 * BLOCK_SIZE], [101; BLOCK_SIZE]);
+
+        let last = collisions.message(usize::MAX);
+        assert_eq!(last[63 * BLOCK_SIZE], 163);
+        assert_eq!(last[64 * BLOCK_SIZE], 64);
+    }
+
+    #[test]
+    fn challenge() {
+        let f = Md::new(16);
+        let g = Md::new(24);
+        let (a, b) = cascade_collision(&f, &g);
+        assert_ne!(a, b);
+        assert_eq!(f.hash(&a), f.hash(&b));
+        assert_eq!(g.hash(&a), g.hash(&b));
+    }
+}
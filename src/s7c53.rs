@@ -0,0 +1,142 @@
+use crate::s7c52::{find_collision, Block, Md, State, BLOCK_SIZE};
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+
+// A (k, k + 2^k - 1) expandable message: for any length in that range
+// (in blocks), a message of that length that takes the initial state to
+// the final one.
+//
+// Piece i collides a single block with 2^i dummy blocks followed by one
+// more block, so it can be 1 or 2^i + 1 blocks long.
+#[derive(Debug)]
+pub struct Expandable {
+    // (short, long) for each piece, biggest first
+    pieces: Vec<(Block, Vec<u8>)>,
+    pub state: State,
+}
+
+impl Expandable {
+    pub fn find(md: &Md, k: usize) -> Self {
+        let mut pieces = Vec::with_capacity(k);
+        let mut state = md.initial();
+        for i in (0..k).rev() {
+            let dummy = vec![0; BLOCK_SIZE << i];
+            let after_dummy = md.iterate(state, &dummy);
+            let (short, last, next) = find_collision(md, (state, after_dummy));
+
+            let mut long = dummy;
+            long.extend_from_slice(&last);
+            pieces.push((short, long));
+            state = next;
+        }
+        Self { pieces, state }
+    }
+
+    pub fn min_blocks(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn max_blocks(&self) -> usize {
+        self.pieces.len() + (1 << self.pieces.len()) - 1
+    }
+
+    // The extra length beyond k blocks is picked bit by bit
+    pub fn message(&self, blocks: usize) -> Option<Vec<u8>> {
+        if !(self.min_blocks()..=self.max_blocks()).contains(&blocks) {
+            return None;
+        }
+
+        let extra = blocks - self.min_blocks();
+        let k = self.pieces.len();
+        let mut msg = Vec::with_capacity(blocks * BLOCK_SIZE);
+        for (j, (short, long)) in self.pieces.iter().enumerate() {
+            match extra >> (k - 1 - j) & 1 {
+                0 => msg.extend_from_slice(short),
+                _ => msg.extend_from_slice(long),
+            }
+        }
+        Some(msg)
+    }
+}
+
+// A different message with the same hash as msg, which must be at least
+// k + 1 and at most 2^k + k blocks long. The length is part of the
+// padding, so the second preimage has to be the same length.
+//
+// Find a block that takes the final state of a (k, k + 2^k - 1)
+// expandable message to one of the intermediate states of msg, say after
+// j blocks. The expandable message stretched to j - 1 blocks, that
+// bridge block, and what follows block j in msg is a second preimage.
+//
+// With 2^k intermediate states, finding the bridge takes about
+// 2^(bits - k) tries, instead of 2^bits for a direct second preimage.
+pub fn second_preimage(md: &Md, msg: &[u8], k: usize) -> Option<Vec<u8>> {
+    // Intermediate states we can bridge to, with the number of blocks
+    // before them
+    let mut targets = HashMap::new();
+    let mut state = md.initial();
+    for (i, block) in msg.chunks_exact(BLOCK_SIZE).enumerate() {
+        state = md.compress(state, block.try_into().unwrap());
+        let j = i + 1;
+        if (k + 1..=k + (1 << k)).contains(&j) {
+            targets.entry(state).or_insert(j);
+        }
+    }
+    if targets.is_empty() {
+        return None;
+    }
+
+    let expandable = Expandable::find(md, k);
+    let mut rng = thread_rng();
+    loop {
+        let bridge: Block = rng.gen();
+        let Some(&j) = targets.get(&md.compress(expandable.state, &bridge)) else {
+            continue;
+        };
+
+        let mut forged = expandable.message(j - 1)?;
+        forged.extend_from_slice(&bridge);
+        forged.extend_from_slice(&msg[j * BLOCK_SIZE..]);
+        // Practically never happens, but we'd want a *different* message
+        if forged != msg {
+            return Some(forged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expandable() {
+        let md = Md::new(16);
+        let expandable = Expandable::find(&md, 4);
+        assert_eq!(expandable.min_blocks(), 4);
+        assert_eq!(expandable.max_blocks(), 19);
+
+        for blocks in 4..=19 {
+            let msg = expandable.message(blocks).unwrap();
+            assert_eq!(msg.len(), blocks * BLOCK_SIZE);
+            assert_eq!(md.iterate(md.initial(), &msg), expandable.state);
+        }
+        assert_eq!(expandable.message(3), None);
+        assert_eq!(expandable.message(20), None);
+    }
+
+    #[test]
+    fn challenge() {
+        let md = Md::new(24);
+        let k = 10;
+        let mut msg = vec![0u8; (1 << k) * BLOCK_SIZE + 5];
+        thread_rng().fill(&mut msg[..]);
+
+        let forged = second_preimage(&md, &msg, k).unwrap();
+        assert_ne!(forged, msg);
+        assert_eq!(forged.len(), msg.len());
+        assert_eq!(md.hash(&forged), md.hash(&msg));
+
+        // Too short to bridge to anything
+        assert_eq!(second_preimage(&md, &msg[..k * BLOCK_SIZE], k), None);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::s7c52::{find_collision, Block, Md, State, BLOCK_SIZE};
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+
+// The Nostradamus attack: commit to a hash now, and later make a message
+// with any prefix that hashes to it.
+//
+// The diamond structure is a binary tree of collisions: 2^k leaf states,
+// paired up, with a block for each that takes both of the pair to the
+// same state, and so on up to a single root state. From any leaf, there's
+// a path of k blocks to the root.
+#[derive(Debug)]
+pub struct Diamond {
+    md: Md,
+    leaves: HashMap<State, usize>,
+    // For each level from the leaves up, the block for each node
+    levels: Vec<Vec<Block>>,
+    root: State,
+    // How long the prefix can be, in blocks, so that we know the total
+    // length (and padding) in advance
+    prefix_blocks: usize,
+}
+
+impl Diamond {
+    // At most md.bits() levels, as there aren't enough distinct states
+    // for more leaves
+    pub fn build(md: &Md, k: usize, prefix_blocks: usize) -> Self {
+        assert!(k <= md.bits() as usize);
+        let mut rng = thread_rng();
+        let mut states: Vec<State> = Vec::with_capacity(1 << k);
+        while states.len() < 1 << k {
+            let state = rng.gen::<State>() >> (32 - md.bits());
+            if !states.contains(&state) {
+                states.push(state);
+            }
+        }
+        let leaves = states.iter().enumerate().map(|(i, &s)| (s, i)).collect();
+
+        let mut levels = Vec::with_capacity(k);
+        while states.len() > 1 {
+            let mut blocks = Vec::with_capacity(states.len());
+            let mut next = Vec::with_capacity(states.len() / 2);
+            for pair in states.chunks_exact(2) {
+                let (a, b, state) = find_collision(md, (pair[0], pair[1]));
+                blocks.extend([a, b]);
+                next.push(state);
+            }
+            levels.push(blocks);
+            states = next;
+        }
+
+        Self {
+            md: *md,
+            leaves,
+            levels,
+            root: states[0],
+            prefix_blocks,
+        }
+    }
+
+    fn len(&self) -> usize {
+        (self.prefix_blocks + 1 + self.levels.len()) * BLOCK_SIZE
+    }
+
+    // The hash of every herded message: the root state, padded for their
+    // common length
+    pub fn prediction(&self) -> State {
+        self.md.iterate(self.root, &self.md.padding(self.len()))
+    }
+
+    // The prefix is padded with spaces to the length given when building.
+    // Then a block that links to one of the leaves, which takes about
+    // 2^(bits - k) tries, and the path from that leaf to the root.
+    pub fn herd(&self, prefix: &[u8]) -> Option<Vec<u8>> {
+        let prefix_len = self.prefix_blocks * BLOCK_SIZE;
+        if prefix.len() > prefix_len {
+            return None;
+        }
+        let mut msg = prefix.to_vec();
+        msg.resize(prefix_len, b' ');
+        let state = self.md.iterate(self.md.initial(), &msg);
+
+        let mut rng = thread_rng();
+        let (link, mut index) = loop {
+            let link: Block = rng.gen();
+            if let Some(&i) = self.leaves.get(&self.md.compress(state, &link)) {
+                break (link, i);
+            }
+        };
+
+        msg.extend_from_slice(&link);
+        for blocks in &self.levels {
+            msg.extend_from_slice(&blocks[index]);
+            index /= 2;
+        }
+        Some(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diamond() {
+        let md = Md::new(16);
+        let diamond = Diamond::build(&md, 4, 1);
+        assert_eq!(diamond.leaves.len(), 16);
+        assert_eq!(diamond.levels.len(), 4);
+
+        // Every leaf leads to the root
+        for (&leaf, &i) in &diamond.leaves {
+            let mut state = leaf;
+            let mut index = i;
+            for blocks in &diamond.levels {
+                state = md.compress(state, &blocks[index]);
+                index /= 2;
+            }
+            assert_eq!(state, diamond.root);
+        }
+    }
+
+    #[test]
+    fn challenge() {
+        let md = Md::new(20);
+        let diamond = Diamond::build(&md, 8, 4);
+        let prediction = diamond.prediction();
+
+        // After the season
+        let results = [
+            &b"Final scores: Yankees 4, Red Sox 2; Mets 1, Phillies 7"[..],
+            b"Final scores: Yankees 0, Red Sox 9; Mets 3, Phillies 2",
+        ];
+        for result in results {
+            let msg = diamond.herd(result).unwrap();
+            assert!(msg.starts_with(result));
+            assert_eq!(md.hash(&msg), prediction);
+        }
+
+        assert_eq!(diamond.herd(&[b'x'; 65]), None);
+    }
+}